@@ -1,5 +1,9 @@
+// with the "safe" feature the value layer has no unsafe code at all
+#[cfg_attr(feature = "safe", forbid(unsafe_code))]
 pub mod pv;
 pub mod pl;
 
-pub use pv::{PvInvalid, PvNull, PvBool, PvInt, PvString, PvArray, PvObject, Pv};
+pub use pv::{PvInvalid, PvNull, PvBool, PvInt, PvFloat, PvString, PvBytes, PvArray, PvObject, Pv, ParseError, JsonIndent, JsonOptions, JsonColor, JsonColors};
+#[cfg(feature = "serde")]
+pub use pv::{from_pv, to_pv, SerdeError};
 pub use pl::bytecode::{PlInstruction, PlState};
\ No newline at end of file
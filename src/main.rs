@@ -8,18 +8,20 @@ fn main() {
 		PlInstruction::PushInt(3),
 		PlInstruction::PrintTop,
 		PlInstruction::Debug,
-		PlInstruction::PushFrame,
+		PlInstruction::Call(3, 0),
+		PlInstruction::PrintTop,
+		PlInstruction::Debug,
+		PlInstruction::Return,
+		// called from the top
 		PlInstruction::PushInt(23),
 		PlInstruction::Debug,
-		PlInstruction::PushFrame,
+		PlInstruction::Call(2, 0),
+		PlInstruction::Add,
+		PlInstruction::Ret,
+		// called from inside that
 		PlInstruction::PushInt(8),
 		PlInstruction::Debug,
-		PlInstruction::PopFrame,
-		PlInstruction::PushInt(17),
-		PlInstruction::Debug,
-		PlInstruction::PopFrame,
-		PlInstruction::Debug,
-		PlInstruction::Return,
+		PlInstruction::Ret,
 	]);
 
 	dbg!(state.execute());
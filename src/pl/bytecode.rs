@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use crate::pv::Pv;
+use crate::pv::{memory, memory_error, Pv};
 use crate::pl::stack::PlStack;
 
 #[derive(Copy, Clone, Debug)]
@@ -13,8 +13,50 @@ pub enum PlInstruction {
 	PushNull,
 	PrintTop,
 	Debug,
-	PushFrame,
-	PopFrame,
+	// these pop two values and push the result
+	// the top value is the right hand side
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Mod,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+	// these pop one value and push the result
+	Not,
+	Neg,
+	// these pop the condition and jump if it's false (or true)
+	// like jq, only null and false are false
+	JumpIfFalse(isize),
+	JumpIfTrue(isize),
+	// for `a and b` and `a or b`, with `a` on the stack
+	// if `a` decides the answer, it's replaced with that and these jump (to after `b`)
+	// otherwise `a` is popped so `b` can run (and then ToBool it)
+	And(isize),
+	Or(isize),
+	// replace the top value with its truthiness
+	ToBool,
+	// push a frame returning to the next instruction, then jump like Jump does
+	// the second one is how many frames out from the top one the called code was defined in
+	// (0 if it was defined in the caller), which is where its upvalues come from
+	Call(isize, usize),
+	// pop the top value and the frame, jump back to where the frame was pushed
+	// and push the value there
+	// with no frame left, this ends the program with the value instead
+	// with no value on top of the frame, it's an error
+	Ret,
+	// push local `n` of the top frame
+	LoadLocal(usize),
+	// pop a value and store it in local `n` of the top frame
+	StoreLocal(usize),
+	// push local `n` of the frame `depth` frames out from the top one
+	// going out to where each one's code was defined, not to whatever called it
+	// (LoadUpvalue(0, n) is the same as LoadLocal(n))
+	LoadUpvalue(usize, usize),
 }
 
 #[derive(Clone, Debug)]
@@ -28,8 +70,9 @@ impl PlInstructionPointer {
 		PlInstructionPointer {bytecode: Rc::from(bytecode), counter: 0}
 	}
 
-	fn get(&self) -> PlInstruction {
-		return self.bytecode[<isize as TryInto<usize>>::try_into(self.counter).unwrap()]
+	// None if it's been jumped outside of the bytecode
+	fn get(&self) -> Option<PlInstruction> {
+		usize::try_from(self.counter).ok().and_then(|i| self.bytecode.get(i).copied())
 	}
 }
 
@@ -45,6 +88,25 @@ impl std::ops::AddAssign<&isize> for PlInstructionPointer {
 	}
 }
 
+// an invalid for a Call or LoadUpvalue going out further than there are frames
+fn frame_error(depth: usize) -> Pv {
+	Pv::invalid_with_msg(format!("there's no frame {} frames out", depth).as_str().into())
+}
+
+// an invalid for an instruction that needs a value when there isn't one on top of the stack
+fn empty_error() -> Pv {
+	Pv::invalid_with_msg("there's no value on the stack".into())
+}
+
+// an invalid for a local that can't be used
+fn local_error(depth: usize, n: usize) -> Pv {
+	if n >= PlStack::MAX_LOCALS {
+		Pv::invalid_with_msg(format!("local {} is past the last one ({})", n, PlStack::MAX_LOCALS - 1).as_str().into())
+	} else {
+		frame_error(depth)
+	}
+}
+
 pub struct PlState {
 	instruction_pointer: PlInstructionPointer,
 	stack: PlStack,
@@ -59,8 +121,15 @@ impl PlState {
 	}
 
 	// execute one instruction
+	// if it went over this thread's memory limit, that's an error that stops the program
 	pub fn executeone(&mut self) -> Option<Pv> {
-		let instruction = self.instruction_pointer.get();
+		memory::within_limit(|| self.execute_instruction()).unwrap_or_else(|| Some(memory_error()))
+	}
+
+	fn execute_instruction(&mut self) -> Option<Pv> {
+		let Some(instruction) = self.instruction_pointer.get() else {
+			return Some(Pv::invalid_with_msg("jumped outside of the bytecode".into()));
+		};
 		self.instruction_pointer += 1;
 		match instruction {
 			PlInstruction::Nop => None,
@@ -73,7 +142,7 @@ impl PlState {
 				None
 			},
 			PlInstruction::Return => {
-				Some(self.stack.top())
+				Some(self.stack.top().unwrap_or_else(empty_error))
 			},
 			PlInstruction::PushInt(n) => {
 				self.stack.push(Pv::int(n));
@@ -84,22 +153,122 @@ impl PlState {
 				None
 			},
 			PlInstruction::PrintTop => {
-				dbg!(self.stack.top());
+				let Some(value) = self.stack.top() else {
+					return Some(empty_error());
+				};
+				dbg!(value);
 				None
 			},
 			PlInstruction::Debug => {
 				dbg!(&self.stack);
 				None
 			},
-			PlInstruction::PushFrame => {
-				self.stack.push_frame(self.instruction_pointer.clone());
+			PlInstruction::Add => self.binary(|v1, v2| v1 + v2),
+			PlInstruction::Sub => self.binary(|v1, v2| v1 - v2),
+			PlInstruction::Mul => self.binary(|v1, v2| v1 * v2),
+			PlInstruction::Div => self.binary(|v1, v2| v1 / v2),
+			PlInstruction::Mod => self.binary(|v1, v2| v1 % v2),
+			PlInstruction::Eq => self.binary(|v1, v2| Pv::bool(v1 == v2)),
+			PlInstruction::Ne => self.binary(|v1, v2| Pv::bool(v1 != v2)),
+			PlInstruction::Lt => self.binary(|v1, v2| Pv::bool(v1 < v2)),
+			PlInstruction::Le => self.binary(|v1, v2| Pv::bool(v1 <= v2)),
+			PlInstruction::Gt => self.binary(|v1, v2| Pv::bool(v1 > v2)),
+			PlInstruction::Ge => self.binary(|v1, v2| Pv::bool(v1 >= v2)),
+			PlInstruction::Not => self.unary(|v| Pv::bool(!v.is_truthy())),
+			PlInstruction::Neg => self.unary(|v| -v),
+			PlInstruction::JumpIfFalse(offset) => self.jump_if(false, offset),
+			PlInstruction::JumpIfTrue(offset) => self.jump_if(true, offset),
+			PlInstruction::And(offset) => self.short_circuit(false, offset),
+			PlInstruction::Or(offset) => self.short_circuit(true, offset),
+			PlInstruction::ToBool => self.unary(|v| Pv::bool(v.is_truthy())),
+			PlInstruction::Call(offset, depth) => {
+				if !self.stack.push_frame(self.instruction_pointer.clone(), depth) {
+					return Some(frame_error(depth));
+				}
+				self.instruction_pointer += offset;
 				None
 			},
-			PlInstruction::PopFrame => {
-				dbg!(self.stack.pop_frame());
+			PlInstruction::Ret => {
+				let Some(value) = self.stack.try_pop() else {
+					return Some(Pv::invalid_with_msg("nothing to return".into()));
+				};
+				match self.stack.pop_frame() {
+					Some(retaddr) => {
+						self.instruction_pointer = retaddr;
+						self.stack.push(value);
+						None
+					},
+					None => Some(value),
+				}
+			},
+			PlInstruction::LoadLocal(n) => self.load_local(0, n),
+			PlInstruction::StoreLocal(n) => {
+				let Some(value) = self.stack.try_pop() else {
+					return Some(empty_error());
+				};
+				if !self.stack.set_local(n, value) {
+					return Some(local_error(0, n));
+				}
 				None
 			},
+			PlInstruction::LoadUpvalue(depth, n) => self.load_local(depth, n),
+		}
+	}
+
+	fn load_local(&mut self, depth: usize, n: usize) -> Option<Pv> {
+		let Some(value) = self.stack.get_local(depth, n) else {
+			return Some(local_error(depth, n));
+		};
+		self.stack.push(value);
+		None
+	}
+
+	// push the result of an operation
+	// an invalid result is an error, so it stops the program instead
+	fn push_result(&mut self, result: Pv) -> Option<Pv> {
+		if !result.is_valid() {
+			return Some(result);
 		}
+		self.stack.push(result);
+		None
+	}
+
+	// pop the top value, and jump if its truthiness is `jumps`
+	fn jump_if(&mut self, jumps: bool, offset: isize) -> Option<Pv> {
+		let Some(v) = self.stack.try_pop() else {
+			return Some(empty_error());
+		};
+		if v.is_truthy() == jumps {
+			self.instruction_pointer += offset;
+		}
+		None
+	}
+
+	// if the top value's truthiness is `decides`, replace it with that and jump
+	// otherwise pop it
+	fn short_circuit(&mut self, decides: bool, offset: isize) -> Option<Pv> {
+		let Some(v) = self.stack.try_pop() else {
+			return Some(empty_error());
+		};
+		if v.is_truthy() == decides {
+			self.stack.push(Pv::bool(decides));
+			self.instruction_pointer += offset;
+		}
+		None
+	}
+
+	fn binary(&mut self, op: fn(Pv, Pv) -> Pv) -> Option<Pv> {
+		let (Some(v2), Some(v1)) = (self.stack.try_pop(), self.stack.try_pop()) else {
+			return Some(empty_error());
+		};
+		self.push_result(op(v1, v2))
+	}
+
+	fn unary(&mut self, op: fn(Pv) -> Pv) -> Option<Pv> {
+		let Some(v) = self.stack.try_pop() else {
+			return Some(empty_error());
+		};
+		self.push_result(op(v))
 	}
 
 	// execute for up to `steps` steps
@@ -111,7 +280,7 @@ impl PlState {
 			}
 		}
 
-		return None
+		None
 	}
 
 	pub fn execute(&mut self) -> Pv {
@@ -121,4 +290,287 @@ impl PlState {
 			}
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_arithmetic() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(7),
+			PlInstruction::PushInt(3),
+			PlInstruction::Sub,
+			PlInstruction::PushInt(5),
+			PlInstruction::Mul,
+			PlInstruction::PushInt(3),
+			PlInstruction::Mod,
+			PlInstruction::Neg,
+			PlInstruction::Return,
+		]);
+		assert_eq!(state.execute(), Pv::int(-2));
+	}
+
+	#[test]
+	fn test_comparison() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(1),
+			PlInstruction::PushInt(2),
+			PlInstruction::Lt,
+			PlInstruction::PushNull,
+			PlInstruction::Not,
+			PlInstruction::Eq,
+			PlInstruction::Return,
+		]);
+		assert_eq!(state.execute(), Pv::bool(true));
+	}
+
+	#[test]
+	fn test_invalid_result() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(1),
+			PlInstruction::PushInt(0),
+			PlInstruction::Div,
+			PlInstruction::PushInt(5),
+			PlInstruction::Return,
+		]);
+		let result = state.execute();
+		assert!(!result.is_valid());
+		let Pv::Invalid(invalid) = result else {unreachable!()};
+		assert_eq!(invalid.msg(), Some("int (1) and int (0) cannot be divided because the divisor is zero".into()));
+	}
+
+	// if . < 5 then 1 elif . < 10 then 2 else 3 end
+	fn if_chain(n: isize) -> Pv {
+		PlState::new([
+			PlInstruction::PushInt(n),
+			PlInstruction::PushInt(5),
+			PlInstruction::Lt,
+			PlInstruction::JumpIfFalse(2),
+			PlInstruction::PushInt(1),
+			PlInstruction::Jump(7),
+			PlInstruction::PushInt(n),
+			PlInstruction::PushInt(10),
+			PlInstruction::Lt,
+			PlInstruction::JumpIfFalse(2),
+			PlInstruction::PushInt(2),
+			PlInstruction::Jump(1),
+			PlInstruction::PushInt(3),
+			PlInstruction::Return,
+		]).execute()
+	}
+
+	#[test]
+	fn test_if_chain() {
+		assert_eq!(if_chain(1), Pv::int(1));
+		assert_eq!(if_chain(7), Pv::int(2));
+		assert_eq!(if_chain(12), Pv::int(3));
+	}
+
+	#[test]
+	fn test_jump_if_true() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(0),
+			PlInstruction::JumpIfTrue(1),
+			PlInstruction::Hey,
+			PlInstruction::PushNull,
+			PlInstruction::Return,
+		]);
+		assert_eq!(state.execute(), Pv::null());
+	}
+
+	// `a and b` or `a or b`, where `a` and `b` are pushed by the given instructions
+	fn logic(a: PlInstruction, op: fn(isize) -> PlInstruction, b: PlInstruction) -> Pv {
+		PlState::new([
+			a,
+			op(2),
+			b,
+			PlInstruction::ToBool,
+			PlInstruction::Return,
+		]).execute()
+	}
+
+	#[test]
+	fn test_and_or() {
+		let and = PlInstruction::And;
+		let or = PlInstruction::Or;
+		assert_eq!(logic(PlInstruction::PushInt(1), and, PlInstruction::PushInt(2)), Pv::bool(true));
+		assert_eq!(logic(PlInstruction::PushInt(1), and, PlInstruction::PushNull), Pv::bool(false));
+		assert_eq!(logic(PlInstruction::PushNull, and, PlInstruction::PushInt(2)), Pv::bool(false));
+		assert_eq!(logic(PlInstruction::PushNull, or, PlInstruction::PushInt(2)), Pv::bool(true));
+		assert_eq!(logic(PlInstruction::PushNull, or, PlInstruction::PushNull), Pv::bool(false));
+		assert_eq!(logic(PlInstruction::PushInt(1), or, PlInstruction::PushNull), Pv::bool(true));
+	}
+
+	#[test]
+	fn test_call() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(5),
+			PlInstruction::Call(3, 0),
+			PlInstruction::Add,
+			PlInstruction::Ret,
+			PlInstruction::Hey,
+			// called
+			PlInstruction::PushInt(2),
+			PlInstruction::PushInt(21),
+			PlInstruction::Mul,
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::int(47));
+	}
+
+	#[test]
+	fn test_nested_call() {
+		let mut state = PlState::new([
+			PlInstruction::Call(2, 0),
+			PlInstruction::Ret,
+			PlInstruction::Hey,
+			// called first
+			PlInstruction::PushInt(1),
+			PlInstruction::Call(3, 0),
+			PlInstruction::Sub,
+			PlInstruction::Ret,
+			PlInstruction::Hey,
+			// called second, leaving things on the stack that go away with the frame
+			PlInstruction::PushInt(7),
+			PlInstruction::PushInt(8),
+			PlInstruction::PushInt(9),
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::int(-8));
+	}
+
+	#[test]
+	fn test_ret_without_value() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(1),
+			PlInstruction::Call(1, 0),
+			PlInstruction::Return,
+			// called, with nothing to return
+			PlInstruction::Ret,
+		]);
+		let Pv::Invalid(invalid) = state.execute() else {panic!("expected an invalid")};
+		assert_eq!(invalid.msg(), Some("nothing to return".into()));
+		// with no frame and an empty stack too
+		let mut state = PlState::new([
+			PlInstruction::Ret,
+		]);
+		let Pv::Invalid(invalid) = state.execute() else {panic!("expected an invalid")};
+		assert_eq!(invalid.msg(), Some("nothing to return".into()));
+	}
+
+	#[test]
+	fn test_bad_bytecode() {
+		fn error(bytecode: [PlInstruction; 3]) -> Option<Pv> {
+			let Pv::Invalid(invalid) = PlState::new(bytecode).execute() else {panic!("expected an invalid")};
+			invalid.msg()
+		}
+		let empty = Some("there's no value on the stack".into());
+		assert_eq!(error([PlInstruction::PushInt(1), PlInstruction::Add, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::Neg, PlInstruction::Ret, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::JumpIfFalse(0), PlInstruction::Ret, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::JumpIfTrue(0), PlInstruction::Ret, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::And(0), PlInstruction::Ret, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::StoreLocal(0), PlInstruction::Ret, PlInstruction::Ret]), empty);
+		assert_eq!(error([PlInstruction::Return, PlInstruction::Ret, PlInstruction::Ret]), empty);
+		// a frame on top doesn't count as a value
+		assert_eq!(error([PlInstruction::PushInt(1), PlInstruction::Call(0, 0), PlInstruction::Add]), empty);
+		assert_eq!(error([PlInstruction::Jump(5), PlInstruction::Ret, PlInstruction::Ret]), Some("jumped outside of the bytecode".into()));
+		assert_eq!(error([PlInstruction::Jump(-3), PlInstruction::Ret, PlInstruction::Ret]), Some("jumped outside of the bytecode".into()));
+		assert_eq!(error([PlInstruction::Nop, PlInstruction::Nop, PlInstruction::Nop]), Some("jumped outside of the bytecode".into()));
+	}
+
+	#[test]
+	fn test_locals() {
+		// 3 as $x | 4 as $y | $x * $y - $x
+		let mut state = PlState::new([
+			PlInstruction::PushInt(3),
+			PlInstruction::StoreLocal(0),
+			PlInstruction::PushInt(4),
+			PlInstruction::StoreLocal(1),
+			PlInstruction::LoadLocal(0),
+			PlInstruction::LoadLocal(1),
+			PlInstruction::Mul,
+			PlInstruction::LoadLocal(0),
+			PlInstruction::Sub,
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::int(9));
+
+		// locals that were never stored to are null
+		let mut state = PlState::new([
+			PlInstruction::LoadLocal(5),
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::null());
+	}
+
+	#[test]
+	fn test_bad_locals() {
+		fn error(bytecode: [PlInstruction; 3]) -> Option<Pv> {
+			let Pv::Invalid(invalid) = PlState::new(bytecode).execute() else {panic!("expected an invalid")};
+			invalid.msg()
+		}
+		let past = format!("local {} is past the last one ({})", usize::MAX, PlStack::MAX_LOCALS - 1);
+		assert_eq!(error([PlInstruction::PushInt(1), PlInstruction::StoreLocal(usize::MAX), PlInstruction::Ret]), Some(past.as_str().into()));
+		assert_eq!(error([PlInstruction::LoadLocal(usize::MAX), PlInstruction::Ret, PlInstruction::Ret]), Some(past.as_str().into()));
+		assert_eq!(error([PlInstruction::LoadUpvalue(1, 0), PlInstruction::Ret, PlInstruction::Ret]), Some("there's no frame 1 frames out".into()));
+		assert_eq!(error([PlInstruction::Call(1, 1), PlInstruction::Ret, PlInstruction::Ret]), Some("there's no frame 1 frames out".into()));
+		// the last slot is still fine
+		let mut state = PlState::new([
+			PlInstruction::PushInt(3),
+			PlInstruction::StoreLocal(PlStack::MAX_LOCALS - 1),
+			PlInstruction::LoadLocal(PlStack::MAX_LOCALS - 1),
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::int(3));
+	}
+
+	#[test]
+	fn test_frame_locals() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(10),
+			PlInstruction::StoreLocal(0),
+			PlInstruction::Call(3, 0),
+			PlInstruction::LoadLocal(0),
+			PlInstruction::Sub,
+			PlInstruction::Ret,
+			// called, with its own locals and the caller's as upvalues
+			PlInstruction::PushInt(5),
+			PlInstruction::StoreLocal(0),
+			PlInstruction::LoadLocal(0),
+			PlInstruction::LoadUpvalue(1, 0),
+			PlInstruction::Mul,
+			PlInstruction::Ret,
+		]);
+		// 5 * 10 - 10, and the caller's local wasn't touched
+		assert_eq!(state.execute(), Pv::int(40));
+	}
+
+	#[test]
+	fn test_upvalues_through_calls() {
+		let mut state = PlState::new([
+			PlInstruction::PushInt(10),
+			PlInstruction::StoreLocal(0),
+			PlInstruction::Call(2, 0),
+			PlInstruction::Ret,
+			PlInstruction::Hey,
+			// f, defined at the top
+			PlInstruction::PushInt(99),
+			PlInstruction::StoreLocal(0),
+			PlInstruction::Call(3, 1),
+			PlInstruction::Call(4, 0),
+			PlInstruction::Sub,
+			PlInstruction::Ret,
+			// g, also defined at the top but called from f
+			// so its upvalue is the top's local and not f's
+			PlInstruction::LoadUpvalue(1, 0),
+			PlInstruction::Ret,
+			// h, defined in f
+			PlInstruction::LoadUpvalue(1, 0),
+			PlInstruction::Ret,
+		]);
+		assert_eq!(state.execute(), Pv::int(10 - 99));
+	}
+}
@@ -1,7 +1,7 @@
 use crate::pv::private::PvpArray;
 use crate::pl::bytecode::PlInstructionPointer;
 
-use crate::pv::Pv;
+use crate::pv::{Pv, PvArray};
 
 #[derive(Debug, Clone)]
 struct PlStackFrame {
@@ -9,7 +9,13 @@ struct PlStackFrame {
     // return address
     // maybe last frame index?
     retaddr: PlInstructionPointer,
+    // the frame that called this one, which Ret goes back to
     lastframe: isize,
+    // the frame this one's code was defined in, which upvalues come from
+    staticframe: isize,
+    // local variable slots
+    // ones that were never stored to are null
+    locals: PvArray,
 }
 
 #[derive(Debug, Clone)]
@@ -22,58 +28,127 @@ enum PlStackElement {
 pub struct PlStack {
     data: PvpArray<PlStackElement>,
     topframe: isize,
+    // the locals for code outside of any frame
+    rootlocals: PvArray,
 }
 
 impl PlStack {
+    // the highest local slot is MAX_LOCALS - 1
+    // so a bad slot number can't make a frame's locals huge
+    pub const MAX_LOCALS: usize = 4096;
+
     pub fn new() -> Self {
-        PlStack {data: PvpArray::<PlStackElement>::new_empty(), topframe: -1}
+        PlStack {data: PvpArray::<PlStackElement>::new_empty(), topframe: -1, rootlocals: PvArray::new_empty()}
     }
 
     pub fn push(&mut self, other: Pv) {
         self.data.append(PlStackElement::Value(other));
     }
 
-    pub fn push_frame(&mut self, retaddr: PlInstructionPointer) {
+    // `depth` is how many frames out from the top one the new frame's code was defined in
+    // false (pushing nothing) if there's no frame that far out
+    pub fn push_frame(&mut self, retaddr: PlInstructionPointer, depth: usize) -> bool {
         let topframe = self.data.len();
+        let Some(staticframe) = self.frame_index(depth) else {
+            return false;
+        };
         self.data.append(PlStackElement::Frame(PlStackFrame {
             retaddr,
             lastframe: self.topframe,
+            staticframe,
+            locals: PvArray::new_empty(),
         }));
         self.topframe = topframe.try_into().unwrap();
+        true
     }
 
-    pub fn pop(&mut self) {
-        if let PlStackElement::Value(_) = self.topelement() {
+    // None if there's no value on top (it's empty, or a frame is)
+    pub fn try_pop(&mut self) -> Option<Pv> {
+        if let Some(PlStackElement::Value(v)) = self.data.get_data().last() {
+            let v = v.clone();
             self.data.pop();
+            Some(v)
         } else {
-            panic!("can't pop a stack frame :/");
+            None
         }
     }
 
+    // None if there's no frame to pop
     pub fn pop_frame(&mut self) -> Option<PlInstructionPointer> {
-        if self.topframe == -1 {
+        let index = usize::try_from(self.topframe).ok()?;
+        let Some(PlStackElement::Frame(frame)) = self.data.get_data().get(index).cloned() else {
             return None;
-        }
-        let frame = self.data.get(self.topframe.try_into().unwrap());
-        if let PlStackElement::Frame(frame) = frame {
-            self.data.popn(self.data.len() - <isize as TryInto<usize>>::try_into(self.topframe).unwrap());
-            self.topframe = frame.lastframe;
-            return Some(frame.retaddr);
+        };
+        self.data.popn(self.data.len() - index);
+        self.topframe = frame.lastframe;
+        Some(frame.retaddr)
+    }
+
+    // None if there's no value on top (it's empty, or a frame is)
+    pub fn top(&self) -> Option<Pv> {
+        if let Some(PlStackElement::Value(v)) = self.data.get_data().last() {
+            Some(v.clone())
         } else {
-            panic!("can't pop a non stack frame :/");
+            None
         }
     }
 
-    pub fn top(&self) -> Pv {
-        if let PlStackElement::Value(v) = self.topelement() {
-            v
-        } else {
-            panic!("no value at a stack frame :/");
+    // the index of the frame `depth` frames out from the top one
+    // following where each frame's code was defined, not what called it
+    // (-1 is outside of every frame)
+    // None if there's no frame that far out
+    fn frame_index(&self, depth: usize) -> Option<isize> {
+        let mut index = self.topframe;
+        for _ in 0..depth {
+            if index == -1 {
+                return None;
+            }
+            index = match self.data.get_data().get(index as usize) {
+                Some(PlStackElement::Frame(frame)) => frame.staticframe,
+                _ => return None,
+            };
         }
+        Some(index)
     }
 
-    fn topelement(&self) -> PlStackElement {
-        self.data.get((self.data.len() - 1).try_into().unwrap())
+    // local `n` of the frame `depth` frames out from the top one
+    // None if `n` isn't under MAX_LOCALS or there's no frame that far out
+    pub fn get_local(&self, depth: usize, n: usize) -> Option<Pv> {
+        if n >= PlStack::MAX_LOCALS {
+            return None;
+        }
+        let index = self.frame_index(depth)?;
+        let locals = if index == -1 {
+            &self.rootlocals
+        } else if let Some(PlStackElement::Frame(frame)) = self.data.get_data().get(index as usize) {
+            &frame.locals
+        } else {
+            return None;
+        };
+        // ones past the end were never stored to
+        Some(locals.as_slice().get(n).cloned().into())
+    }
+
+    // set local `n` of the top frame
+    // false (changing nothing) if `n` isn't under MAX_LOCALS or there's no top frame to change
+    pub fn set_local(&mut self, n: usize, value: Pv) -> bool {
+        let Some(n) = isize::try_from(n).ok().filter(|&n| n < PlStack::MAX_LOCALS as isize) else {
+            return false;
+        };
+        let locals = if self.topframe == -1 {
+            &mut self.rootlocals
+        } else if let Some(PlStackElement::Frame(frame)) = self.data.make_mut().get_mut(self.topframe as usize) {
+            &mut frame.locals
+        } else {
+            return false;
+        };
+        locals.set(n, value)
+    }
+}
+
+impl Default for PlStack {
+    fn default() -> Self {
+        PlStack::new()
     }
 }
 
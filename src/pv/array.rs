@@ -12,6 +12,9 @@ pub struct PvArray {
 // because string generic parameters are not allowed
 // and i want a custom name for Debug
 impl PvArray {
+    // the biggest index set() will pad out to (the same limit jq has)
+    pub const MAX_INDEX: usize = 536_870_911;
+
     // allocates enough space for `len` array elements
     pub fn new_empty_sized(size: usize) -> Self {
         PvpArray::<Pv>::new_empty_sized(size).into()
@@ -32,6 +35,115 @@ impl PvArray {
     pub fn append(&mut self, other: Pv) {
         self.data.append(other)
     }
+
+    pub fn as_slice(&self) -> &[Pv] {
+        self.data.get_data()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Pv> {
+        self.as_slice().iter()
+    }
+
+    // negative indices count from the end
+    fn resolve_index(&self, i: isize) -> Option<usize> {
+        let i = if i < 0 {i + self.len() as isize} else {i};
+        usize::try_from(i).ok()
+    }
+
+    pub fn get(&self, i: isize) -> Option<Pv> {
+        self.resolve_index(i).and_then(|i| self.as_slice().get(i).cloned())
+    }
+
+    // like jq, setting past the end pads the array with nulls
+    // returns false (and changes nothing) if a negative index is before the start
+    // or if the index is past MAX_INDEX, or the padding would go over this thread's memory limit
+    pub fn set(&mut self, i: isize, value: Pv) -> bool {
+        let Some(i) = self.resolve_index(i).filter(|&i| i <= PvArray::MAX_INDEX) else {
+            return false;
+        };
+        if self.len() <= i && !self.data.resize(i + 1, Pv::null()) {
+            return false;
+        }
+        self.data.set(i, value);
+        true
+    }
+
+    // indices past either end insert at that end
+    pub fn insert(&mut self, i: isize, value: Pv) {
+        let i = self.resolve_index(i).unwrap_or(0).min(self.len());
+        self.data.insert(i, value)
+    }
+
+    pub fn remove(&mut self, i: isize) -> Option<Pv> {
+        let i = self.resolve_index(i).filter(|&i| i < self.len())?;
+        Some(self.data.remove(i))
+    }
+
+    // the elements from `start` up to (not including) `end`
+    // negative indices count from the end, and both are clamped to the array like jq does
+    pub fn slice(&self, start: isize, end: isize) -> PvArray {
+        let len = self.len() as isize;
+        let clamp = |i: isize| (if i < 0 {i + len} else {i}).clamp(0, len) as usize;
+        let (start, end) = (clamp(start), clamp(end));
+        if start >= end {
+            return PvArray::new_empty();
+        }
+        // shares the allocation until one of them is changed
+        self.data.slice(start, end).into()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len)
+    }
+
+    pub fn reverse(&mut self) {
+        self.data.reverse()
+    }
+
+    // stable, in the order defined by Ord for Pv
+    pub fn sort(&mut self) {
+        self.data.make_mut().sort()
+    }
+
+    // None for an empty array
+    // not just min() and max() because Ord already has those
+    pub fn min_value(&self) -> Option<Pv> {
+        self.iter().min().cloned()
+    }
+
+    pub fn max_value(&self) -> Option<Pv> {
+        self.iter().max().cloned()
+    }
+}
+
+// element by element, and then a shorter array is less
+impl PartialOrd for PvArray {
+    fn partial_cmp(&self, other: &PvArray) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvArray {
+    fn cmp(&self, other: &PvArray) -> std::cmp::Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl<'a> IntoIterator for &'a PvArray {
+    type Item = &'a Pv;
+    type IntoIter = std::slice::Iter<'a, Pv>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 impl std::fmt::Debug for PvArray {
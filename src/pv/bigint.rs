@@ -0,0 +1,322 @@
+use crate::pv::private::PvpArray;
+
+// sign and magnitude
+// the magnitude is little endian base 2^32 with no high zero limbs
+// (so zero has no limbs at all, and is never negative)
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub(crate) struct PvBigInt {
+    negative: bool,
+    limbs: PvpArray<u32>,
+}
+
+// magnitude helpers
+// these all work on plain slices and only the results get put in a PvpArray
+
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    mag
+}
+
+fn mag_cmp(a: &[u32], b: &[u32]) -> std::cmp::Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn mag_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() {(a, b)} else {(b, a)};
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in a.iter().enumerate() {
+        let sum = x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    out.push(carry as u32);
+    trim(out)
+}
+
+// only for a >= b
+fn mag_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        out.push(diff as u32);
+    }
+    trim(out)
+}
+
+fn mag_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let product = x as u64 * y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(out)
+}
+
+// divide by a single limb in place, giving the remainder
+fn mag_divrem_small(mag: &mut Vec<u32>, b: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        *limb = (cur / b as u64) as u32;
+        rem = cur % b as u64;
+    }
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    rem as u32
+}
+
+// `mag` shifted left by `shift` bits (under 32), with one more limb on top for what comes out
+fn mag_shl(mag: &[u32], shift: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(mag.len() + 1);
+    let mut carry = 0u32;
+    for &limb in mag {
+        out.push((limb << shift) | carry);
+        carry = if shift == 0 {0} else {limb >> (32 - shift)};
+    }
+    out.push(carry);
+    out
+}
+
+// long division a limb at a time (Knuth's algorithm D, TAOCP 4.3.1)
+// b must not be zero
+fn mag_divrem(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let mut q = a.to_vec();
+        let r = mag_divrem_small(&mut q, b[0]);
+        return (q, trim(vec![r]));
+    }
+    if mag_cmp(a, b) == std::cmp::Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    // shift both so the top bit of b is set, which keeps each guessed quotient limb at most 2 too big
+    let shift = b[b.len() - 1].leading_zeros();
+    let b = trim(mag_shl(b, shift));
+    let mut a = mag_shl(a, shift);
+    let n = b.len();
+    let mut q = vec![0u32; a.len() - n];
+    for j in (0..q.len()).rev() {
+        // guess from the top two limbs of what's left, and correct it with the next one down
+        let top = ((a[j + n] as u64) << 32) | a[j + n - 1] as u64;
+        let mut qhat = top / b[n - 1] as u64;
+        let mut rhat = top % b[n - 1] as u64;
+        while qhat >> 32 != 0 || qhat * b[n - 2] as u64 > ((rhat << 32) | a[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += b[n - 1] as u64;
+            if rhat >> 32 != 0 {
+                break;
+            }
+        }
+        // subtract qhat * b from this part of a
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let product = qhat * b[i] as u64;
+            let diff = a[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            a[i + j] = diff as u32;
+            borrow = (product >> 32) as i64 - (diff >> 32);
+        }
+        let diff = a[j + n] as i64 - borrow;
+        a[j + n] = diff as u32;
+        q[j] = qhat as u32;
+        // still one too big (this is rare), so add b back
+        if diff < 0 {
+            q[j] = q[j].wrapping_sub(1);
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = a[i + j] as u64 + b[i] as u64 + carry;
+                a[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            a[j + n] = a[j + n].wrapping_add(carry as u32);
+        }
+    }
+    // what's left in a is the remainder, still shifted
+    let mut r = a[..n].to_vec();
+    if shift != 0 {
+        for i in 0..n {
+            r[i] = (r[i] >> shift) | (*r.get(i + 1).unwrap_or(&0) << (32 - shift));
+        }
+    }
+    (trim(q), trim(r))
+}
+
+impl PvBigInt {
+    fn from_parts(negative: bool, mag: Vec<u32>) -> Self {
+        let negative = negative && !mag.is_empty();
+        PvBigInt {negative, limbs: PvpArray::new(&mag)}
+    }
+
+    fn mag(&self) -> &[u32] {
+        self.limbs.get_data()
+    }
+
+    pub fn from_isize(value: isize) -> Self {
+        let mag = value.unsigned_abs() as u64;
+        PvBigInt::from_parts(value < 0, trim(vec![mag as u32, (mag >> 32) as u32]))
+    }
+
+    pub fn to_isize(&self) -> Option<isize> {
+        if self.mag().len() > 2 {
+            return None;
+        }
+        let mag = self.mag().iter().rev().fold(0i128, |acc, &limb| (acc << 32) | limb as i128);
+        isize::try_from(if self.negative {-mag} else {mag}).ok()
+    }
+
+    // the value has to be finite and integral
+    pub fn from_f64(value: f64) -> Self {
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64 - 1075;
+        let mantissa = if exponent == -1075 {
+            (bits & 0xfffffffffffff) << 1
+        } else {
+            (bits & 0xfffffffffffff) | (1 << 52)
+        };
+        let mag = if exponent >= 0 {
+            // shift by whole limbs and then by the leftover bits
+            let mut mag = vec![0u32; exponent as usize / 32];
+            let shifted = (mantissa as u128) << (exponent % 32);
+            mag.extend([shifted as u32, (shifted >> 32) as u32, (shifted >> 64) as u32]);
+            trim(mag)
+        } else {
+            let shifted = mantissa >> (-exponent).min(63);
+            trim(vec![shifted as u32, (shifted >> 32) as u32])
+        };
+        PvBigInt::from_parts(value < 0.0, mag)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mag = self.mag();
+        // the top 64 bits round to the nearest f64 when converted
+        // any bits below that only matter for breaking ties, so they all go in the lowest bit
+        let top = mag.iter().rev().take(3).fold(0u128, |acc, &limb| (acc << 32) | limb as u128);
+        let bits = 128 - top.leading_zeros();
+        let (top, below) = if bits > 64 {(top >> (bits - 64), top & ((1 << (bits - 64)) - 1))} else {(top, 0)};
+        let sticky = below != 0 || mag.iter().rev().skip(3).any(|&limb| limb != 0);
+        let exponent = bits.saturating_sub(64) as usize + 32 * mag.len().saturating_sub(3);
+        // multiplying by a power of two is exact (or overflows to infinity, which is right too)
+        let value = (top as u64 | sticky as u64) as f64 * 2f64.powi(exponent.min(2048) as i32);
+        if self.negative {-value} else {value}
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn neg(&self) -> Self {
+        PvBigInt::from_parts(!self.negative, self.mag().to_vec())
+    }
+
+    pub fn add(&self, other: &PvBigInt) -> Self {
+        if self.negative == other.negative {
+            return PvBigInt::from_parts(self.negative, mag_add(self.mag(), other.mag()));
+        }
+        // the sign of the bigger magnitude wins
+        match mag_cmp(self.mag(), other.mag()) {
+            std::cmp::Ordering::Less => PvBigInt::from_parts(other.negative, mag_sub(other.mag(), self.mag())),
+            _ => PvBigInt::from_parts(self.negative, mag_sub(self.mag(), other.mag())),
+        }
+    }
+
+    pub fn sub(&self, other: &PvBigInt) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &PvBigInt) -> Self {
+        PvBigInt::from_parts(self.negative != other.negative, mag_mul(self.mag(), other.mag()))
+    }
+
+    // truncating division, like isize
+    // so the remainder has the sign of the dividend
+    // None when dividing by zero
+    pub fn divrem(&self, other: &PvBigInt) -> Option<(Self, Self)> {
+        if other.mag().is_empty() {
+            return None;
+        }
+        let (q, r) = mag_divrem(self.mag(), other.mag());
+        Some((
+            PvBigInt::from_parts(self.negative != other.negative, q),
+            PvBigInt::from_parts(self.negative, r),
+        ))
+    }
+
+    pub fn cmp(&self, other: &PvBigInt) -> std::cmp::Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => std::cmp::Ordering::Greater,
+            (true, false) => std::cmp::Ordering::Less,
+            (false, false) => mag_cmp(self.mag(), other.mag()),
+            (true, true) => mag_cmp(other.mag(), self.mag()),
+        }
+    }
+
+    // parse an optional minus sign and then decimal digits
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        // nine digits at a time always fit in a limb
+        // so each chunk is one pass of multiplying and adding in place
+        let mut mag: Vec<u32> = Vec::with_capacity(digits.len() / 9 + 1);
+        for chunk in digits.as_bytes().chunks(9) {
+            let scale = 10u64.pow(chunk.len() as u32);
+            let mut carry = chunk.iter().fold(0u64, |acc, &digit| acc * 10 + (digit - b'0') as u64);
+            for limb in mag.iter_mut() {
+                let cur = *limb as u64 * scale + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry != 0 {
+                mag.push(carry as u32);
+            }
+        }
+        Some(PvBigInt::from_parts(negative, mag))
+    }
+}
+
+impl std::fmt::Display for PvBigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // peel off nine digits at a time from the bottom, dividing in place
+        let mut chunks = Vec::new();
+        let mut mag = self.mag().to_vec();
+        while !mag.is_empty() {
+            chunks.push(mag_divrem_small(&mut mag, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for PvBigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PvBigInt({})", self)
+    }
+}
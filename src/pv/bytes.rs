@@ -1,10 +1,14 @@
 use crate::pv::private::PvpArray;
 
+use crate::pv::PvString;
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct PvBytes {
     data: PvpArray<u8>,
 }
 
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
 // all of these methods are inherited from PvpArray
 // i can't just say "pub type PvBytes = PvpArray<u8>;"
 // because string generic parameters are not allowed
@@ -23,12 +27,93 @@ impl PvBytes {
         PvpArray::<u8>::new(pvs).into()
     }
 
-    pub fn concat(self, other: &PvBytes) -> Self {
-        self.data.concat(&other.data).into()
+    pub fn concat(mut self, other: &PvBytes) -> Self {
+        self.data.concat(&other.data);
+        self
     }
 
     pub fn append(&mut self, other: u8) {
-        self.data.append(other).into()
+        self.data.append(other)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.get_data()
+    }
+
+    // negative indices count from the end
+    pub fn get(&self, i: isize) -> Option<u8> {
+        let i = if i < 0 {i + self.len() as isize} else {i};
+        usize::try_from(i).ok().and_then(|i| self.as_slice().get(i).copied())
+    }
+
+    pub fn to_utf8(&self) -> Result<PvString, std::str::Utf8Error> {
+        std::str::from_utf8(self.as_slice()).map(PvString::new)
+    }
+
+    // invalid sequences become U+FFFD
+    pub fn to_utf8_lossy(&self) -> PvString {
+        PvString::new(&String::from_utf8_lossy(self.as_slice()))
+    }
+
+    pub fn to_hex(&self) -> PvString {
+        let hex: String = self.as_slice().iter().map(|b| format!("{:02x}", b)).collect();
+        PvString::new(&hex)
+    }
+
+    // either case is accepted
+    pub fn from_hex(text: &str) -> Option<PvBytes> {
+        if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut out = PvBytes::new_empty_sized(text.len() / 2);
+        for i in (0..text.len()).step_by(2) {
+            out.append(u8::from_str_radix(&text[i..i + 2], 16).unwrap());
+        }
+        Some(out)
+    }
+
+    // standard alphabet, with padding
+    pub fn to_base64(&self) -> PvString {
+        let mut out = String::with_capacity(self.len().div_ceil(3) * 4);
+        for chunk in self.as_slice().chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        PvString::new(&out)
+    }
+
+    // standard alphabet, and the padding is optional
+    pub fn from_base64(text: &str) -> Option<PvBytes> {
+        let text = text.trim_end_matches('=');
+        if text.len() % 4 == 1 {
+            return None;
+        }
+        let mut out = PvBytes::new_empty_sized(text.len() * 3 / 4);
+        for chunk in text.as_bytes().chunks(4) {
+            let mut n = 0u32;
+            for (i, &c) in chunk.iter().enumerate() {
+                let digit = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+                n |= digit << (18 - 6 * i);
+            }
+            for i in 0..chunk.len() - 1 {
+                out.append((n >> (16 - 8 * i)) as u8);
+            }
+        }
+        Some(out)
     }
 }
 
@@ -38,12 +123,38 @@ impl std::fmt::Debug for PvBytes {
     }
 }
 
+impl PartialOrd for PvBytes {
+    fn partial_cmp(&self, other: &PvBytes) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvBytes {
+    fn cmp(&self, other: &PvBytes) -> std::cmp::Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl std::ops::Index<usize> for PvBytes {
+    type Output = u8;
+
+    fn index(&self, i: usize) -> &u8 {
+        &self.as_slice()[i]
+    }
+}
+
 impl From<&[u8]> for PvBytes {
     fn from(value: &[u8]) -> Self {
         PvBytes::new(value)
     }
 }
 
+impl From<&PvString> for PvBytes {
+    fn from(value: &PvString) -> Self {
+        PvBytes::new(value.get_str().as_bytes())
+    }
+}
+
 impl From<PvpArray<u8>> for PvBytes {
     fn from(value: PvpArray<u8>) -> Self {
         PvBytes {data: value}
@@ -56,4 +167,4 @@ impl std::ops::Add<&PvBytes> for PvBytes {
     fn add(self, other: &PvBytes) -> Self {
         self.concat(other)
     }
-}
\ No newline at end of file
+}
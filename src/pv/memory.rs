@@ -0,0 +1,179 @@
+// keeps count of how many bytes pv values are using, and lets a thread set a limit on that
+// both the count and the limit are per thread
+// (so a host can give every worker running a filter its own budget)
+// bytes are counted on the thread that allocates them, and every allocation remembers
+// whose count that was (its Owner), so freeing it takes the bytes off the same count
+// even with the "sync" feature, when a value made on one thread is dropped on another
+
+use std::cell::Cell;
+
+// with "sync" the count is shared with every Owner made from it
+// so it's an atomic that outlives the thread
+#[cfg(not(feature = "sync"))]
+type Count = Cell<usize>;
+
+#[cfg(feature = "sync")]
+type Count = std::sync::Arc<std::sync::atomic::AtomicUsize>;
+
+thread_local! {
+    static ALLOCATED: Count = Count::default();
+    static LIMIT: Cell<Option<usize>> = const {Cell::new(None)};
+    // whether an allocation has been refused since the innermost within_limit() started
+    static REFUSED: Cell<bool> = const {Cell::new(false)};
+}
+
+#[cfg(not(feature = "sync"))]
+fn count_get(count: &Count) -> usize {
+    count.get()
+}
+
+#[cfg(feature = "sync")]
+fn count_get(count: &Count) -> usize {
+    count.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "sync"))]
+fn count_update(count: &Count, f: impl Fn(usize) -> usize) {
+    count.set(f(count.get()));
+}
+
+#[cfg(feature = "sync")]
+fn count_update(count: &Count, f: impl Fn(usize) -> usize) {
+    // never fails, since `f` always gives Some
+    let _ = count.fetch_update(std::sync::atomic::Ordering::Relaxed, std::sync::atomic::Ordering::Relaxed, |n| Some(f(n)));
+}
+
+// whose count an allocation's bytes are on
+// without "sync" values never leave the thread that made them, so it's always this thread's
+#[cfg(not(feature = "sync"))]
+#[derive(Debug)]
+pub(crate) struct Owner;
+
+#[cfg(feature = "sync")]
+#[derive(Debug)]
+pub(crate) struct Owner(Count);
+
+impl Owner {
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn current() -> Self {
+        Owner
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn current() -> Self {
+        Owner(ALLOCATED.with(|a| a.clone()))
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn with_count<R>(&self, f: impl FnOnce(&Count) -> R) -> R {
+        ALLOCATED.with(f)
+    }
+
+    #[cfg(feature = "sync")]
+    fn with_count<R>(&self, f: impl FnOnce(&Count) -> R) -> R {
+        f(&self.0)
+    }
+
+    // take `bytes` off this owner's count (which never goes below zero)
+    pub(crate) fn free(&self, bytes: usize) {
+        self.with_count(|count| count_update(count, |n| n.saturating_sub(bytes)));
+    }
+
+    // for when this thread is about to change the size of something another thread allocated
+    // its `bytes` so far move to this thread's count, so they all stay on one count
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn adopt(&mut self, _bytes: usize) {}
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn adopt(&mut self, bytes: usize) {
+        let current = Owner::current();
+        if !std::sync::Arc::ptr_eq(&self.0, &current.0) {
+            self.free(bytes);
+            track_alloc(bytes);
+            *self = current;
+        }
+    }
+}
+
+// how many bytes this thread has allocated for pv values right now
+pub fn allocated_bytes() -> usize {
+    ALLOCATED.with(count_get)
+}
+
+// allocations that would take this thread past `limit` bytes are refused
+// and the operators and constructors that wanted them give an invalid instead
+// None means no limit
+pub fn set_memory_limit(limit: Option<usize>) {
+    LIMIT.with(|l| l.set(limit));
+}
+
+pub fn memory_limit() -> Option<usize> {
+    LIMIT.with(|l| l.get())
+}
+
+// whether allocating `extra` more bytes would go over this thread's limit
+pub fn would_exceed(extra: usize) -> bool {
+    match memory_limit() {
+        Some(limit) => allocated_bytes().saturating_add(extra) > limit,
+        None => false,
+    }
+}
+
+// run `f`, and give None instead of its result if anything in it was refused memory
+// whatever `f` was building when that happened is left incomplete, so it shouldn't be used
+// (the Pv operators and constructors use this to give an invalid instead)
+pub fn within_limit<T>(f: impl FnOnce() -> T) -> Option<T> {
+    let outer = REFUSED.with(|r| r.replace(false));
+    let out = f();
+    let refused = REFUSED.with(|r| r.replace(outer || r.get()));
+    (!refused).then_some(out)
+}
+
+// whether something has been refused memory since the innermost within_limit() started
+pub(crate) fn refused() -> bool {
+    REFUSED.with(|r| r.get())
+}
+
+// for allocations that can't be done at all (like ones too big to even have a Layout)
+pub(crate) fn refuse() {
+    REFUSED.with(|r| r.set(true));
+}
+
+// the allocation primitives (PvpArray and the like) ask this before allocating
+// if `bytes` more would go over this thread's limit, they're refused: nothing is counted
+// and the allocation shouldn't happen
+pub(crate) fn try_alloc(bytes: usize) -> bool {
+    if would_exceed(bytes) {
+        refuse();
+        return false;
+    }
+    track_alloc(bytes);
+    true
+}
+
+// for small allocations that can't be skipped (like a PvFixedSize, or the header of an empty array)
+// they're always counted, but going over the limit still counts as a refusal
+pub(crate) fn force_alloc(bytes: usize) {
+    if would_exceed(bytes) {
+        refuse();
+    }
+    track_alloc(bytes);
+}
+
+// try_alloc() if `force` is false, and force_alloc() (giving true) if it's true
+// the primitives force what's sized by elements that already exist somewhere
+// (so a refusal never turns into a value with elements missing)
+// and only ask for what's sized by a number, like a capacity guess or padding
+pub(crate) fn charge(bytes: usize, force: bool) -> bool {
+    if force {
+        force_alloc(bytes);
+        true
+    } else {
+        try_alloc(bytes)
+    }
+}
+
+// counted on this thread, so the allocation's Owner is Owner::current()
+pub(crate) fn track_alloc(bytes: usize) {
+    ALLOCATED.with(|a| count_update(a, |n| n.saturating_add(bytes)));
+}
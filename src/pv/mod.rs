@@ -1,21 +1,40 @@
+#[cfg(not(feature = "safe"))]
 pub mod private; // maybe rename this to implementation?
+#[cfg(feature = "safe")]
+#[path = "private_safe.rs"]
+pub mod private;
+pub mod memory;
+mod bigint;
 mod singletons;
 mod string;
+mod bytes;
 mod array;
 mod object;
+mod parse;
+mod print;
+mod path;
+#[cfg(feature = "serde")]
+mod serialize;
 
-pub use singletons::{PvInvalid, PvNull, PvBool, PvInt};
+pub use singletons::{PvInvalid, PvNull, PvBool, PvInt, PvFloat};
 pub use string::PvString;
+pub use bytes::PvBytes;
 pub use array::PvArray;
 pub use object::PvObject;
+pub use parse::ParseError;
+pub use print::{JsonIndent, JsonOptions, JsonColor, JsonColors};
+#[cfg(feature = "serde")]
+pub use serialize::{from_pv, to_pv, SerdeError};
 
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub enum Pv {
     Invalid(PvInvalid),
     Null(PvNull),
     Bool(PvBool),
     Int(PvInt),
+    Float(PvFloat),
     String(PvString),
+    Bytes(PvBytes),
     Array(PvArray),
     Object(PvObject),
 }
@@ -25,6 +44,10 @@ impl Pv {
         Pv::Invalid(PvInvalid::new())
     }
     
+    pub fn invalid_with_msg(msg: Pv) -> Self {
+        Pv::Invalid(PvInvalid::with_msg(msg))
+    }
+    
     pub fn null() -> Self {
         Pv::Null(PvNull::new())
     }
@@ -37,13 +60,82 @@ impl Pv {
         Pv::Int(PvInt::new(value))
     }
     
+    pub fn float(value: f64) -> Self {
+        Pv::Float(PvFloat::new(value))
+    }
+    
+    // these three give an invalid if they'd go over this thread's memory limit
+
+    pub fn bytes(value: &[u8]) -> Self {
+        memory_guard(|| Pv::Bytes(PvBytes::new(value)))
+    }
+    
     pub fn array() -> Self {
-        Pv::Array(PvArray::new_empty())
+        memory_guard(|| Pv::Array(PvArray::new_empty()))
     }
     
     pub fn object() -> Self {
-        Pv::Object(PvObject::new_empty())
+        memory_guard(|| Pv::Object(PvObject::new_empty()))
+    }
+}
+
+impl Pv {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Pv::Invalid(_))
     }
+
+    // like jq, only null and false are false
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Pv::Null(_)) && !matches!(self, Pv::Bool(v) if !v.value())
+    }
+
+    // what error messages call each type
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Pv::Invalid(_) => "invalid",
+            Pv::Null(_) => "null",
+            Pv::Bool(_) => "boolean",
+            Pv::Int(_) => "int",
+            Pv::Float(_) => "float",
+            Pv::String(_) => "string",
+            Pv::Bytes(_) => "bytes",
+            Pv::Array(_) => "array",
+            Pv::Object(_) => "object",
+        }
+    }
+
+    // the value's json, cut short so it fits in an error message
+    fn error_text(&self) -> String {
+        let text = self.to_json();
+        if text.len() <= 11 {
+            return text;
+        }
+        let mut end = 11;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...", &text[..end])
+    }
+}
+
+// an invalid saying that `v1` and `v2` "cannot be {what}"
+fn op_error(v1: &Pv, v2: &Pv, what: &str) -> Pv {
+    let msg = format!("{} ({}) and {} ({}) cannot be {}", v1.kind_name(), v1.error_text(), v2.kind_name(), v2.error_text(), what);
+    Pv::invalid_with_msg(msg.as_str().into())
+}
+
+pub(crate) fn memory_error() -> Pv {
+    // not "...".into(), which would need memory_error() again if it's refused
+    Pv::invalid_with_msg(Pv::String(PvString::new("memory limit exceeded")))
+}
+
+// `f`'s result, or an invalid if anything in it was refused memory
+// (or if this thread was already over its memory limit)
+fn memory_guard(f: impl FnOnce() -> Pv) -> Pv {
+    if memory::would_exceed(0) {
+        return memory_error();
+    }
+    memory::within_limit(f).unwrap_or_else(memory_error)
 }
 
 impl<T: Into<Pv>> From<Option<T>> for Pv {
@@ -55,15 +147,127 @@ impl<T: Into<Pv>> From<Option<T>> for Pv {
     }
 }
 
+// ints and floats are both just numbers
+// so 1 and 1.0 are equal (and hash the same)
+impl PartialEq for Pv {
+    fn eq(&self, other: &Pv) -> bool {
+        match (self, other) {
+            (Pv::Invalid(v1), Pv::Invalid(v2)) => v1 == v2,
+            (Pv::Null(v1), Pv::Null(v2)) => v1 == v2,
+            (Pv::Bool(v1), Pv::Bool(v2)) => v1 == v2,
+            (Pv::Int(v1), Pv::Int(v2)) => v1 == v2,
+            (Pv::Float(v1), Pv::Float(v2)) => v1 == v2,
+            (Pv::Int(v1), Pv::Float(v2)) | (Pv::Float(v2), Pv::Int(v1)) => v2.to_int().as_ref() == Some(v1),
+            (Pv::String(v1), Pv::String(v2)) => v1 == v2,
+            (Pv::Bytes(v1), Pv::Bytes(v2)) => v1 == v2,
+            (Pv::Array(v1), Pv::Array(v2)) => v1 == v2,
+            (Pv::Object(v1), Pv::Object(v2)) => v1 == v2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Pv {}
+
+impl std::hash::Hash for Pv {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        if let Pv::Float(v) = self {
+            if let Some(v) = v.to_int() {
+                return Pv::Int(v).hash(state);
+            }
+        }
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Pv::Invalid(v) => v.hash(state),
+            Pv::Null(v) => v.hash(state),
+            Pv::Bool(v) => v.hash(state),
+            Pv::Int(v) => v.hash(state),
+            Pv::Float(v) => v.hash(state),
+            Pv::String(v) => v.hash(state),
+            Pv::Bytes(v) => v.hash(state),
+            Pv::Array(v) => v.hash(state),
+            Pv::Object(v) => v.hash(state),
+        }
+    }
+}
+
+impl Pv {
+    // where each kind goes in the total order
+    // all numbers share a rank so that ints and floats interleave
+    fn kind_rank(&self) -> u8 {
+        match self {
+            Pv::Invalid(_) => 0,
+            Pv::Null(_) => 1,
+            Pv::Bool(_) => 2,
+            Pv::Int(_) | Pv::Float(_) => 3,
+            Pv::String(_) => 4,
+            Pv::Bytes(_) => 5,
+            Pv::Array(_) => 6,
+            Pv::Object(_) => 7,
+        }
+    }
+}
+
+// jq's order: null < false < true < numbers < strings < arrays < objects
+// invalids go before everything and bytes go between strings and arrays
+impl PartialOrd for Pv {
+    fn partial_cmp(&self, other: &Pv) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pv {
+    fn cmp(&self, other: &Pv) -> std::cmp::Ordering {
+        match (self, other) {
+            (Pv::Invalid(v1), Pv::Invalid(v2)) => v1.cmp(v2),
+            (Pv::Null(v1), Pv::Null(v2)) => v1.cmp(v2),
+            (Pv::Bool(v1), Pv::Bool(v2)) => v1.cmp(v2),
+            (Pv::Int(v1), Pv::Int(v2)) => v1.cmp(v2),
+            (Pv::Float(v1), Pv::Float(v2)) => v1.cmp(v2),
+            (Pv::Float(v1), Pv::Int(v2)) => v1.cmp_int(v2),
+            (Pv::Int(v1), Pv::Float(v2)) => v2.cmp_int(v1).reverse(),
+            (Pv::String(v1), Pv::String(v2)) => v1.cmp(v2),
+            (Pv::Bytes(v1), Pv::Bytes(v2)) => v1.cmp(v2),
+            (Pv::Array(v1), Pv::Array(v2)) => v1.cmp(v2),
+            (Pv::Object(v1), Pv::Object(v2)) => v1.cmp(v2),
+            _ => self.kind_rank().cmp(&other.kind_rank()),
+        }
+    }
+}
+
+// the float value of any number
+fn number_to_f64(value: &Pv) -> Option<f64> {
+    match value {
+        Pv::Int(v) => Some(v.to_f64()),
+        Pv::Float(v) => Some(v.value()),
+        _ => None,
+    }
+}
+
+// mixed int and float operands are promoted to float
+macro_rules! pv_number_op {
+    ($v1:expr, $v2:expr, $optrait:ident $op:ident, $what:expr) => {
+        match (number_to_f64(&$v1), number_to_f64($v2)) {
+            (Some(v1), Some(v2)) => Pv::float(std::ops::$optrait::$op(v1, v2)),
+            _ => op_error(&$v1, $v2, $what),
+        }
+    }
+}
+
 impl std::ops::Add<&Pv> for Pv {
     type Output = Self;
 
     fn add(self, other: &Pv) -> Self {
-        match (self, other) {
+        memory_guard(|| match (self, other) {
             (Pv::Int(v1), Pv::Int(v2)) => (v1 + v2).into(),
             (Pv::String(v1), Pv::String(v2)) => (v1 + v2).into(),
-            _ => Pv::invalid(),
-        }
+            (Pv::Bytes(v1), Pv::Bytes(v2)) => (v1 + v2).into(),
+            (Pv::Object(mut v1), Pv::Object(v2)) => {
+                v1.merge(v2);
+                v1.into()
+            },
+            (v1, v2) => pv_number_op!(v1, v2, Add add, "added"),
+        })
     }
 }
 
@@ -71,10 +275,10 @@ impl std::ops::Sub<&Pv> for Pv {
     type Output = Self;
 
     fn sub(self, other: &Pv) -> Self {
-        match (self, other) {
+        memory_guard(|| match (self, other) {
             (Pv::Int(v1), Pv::Int(v2)) => (v1 - v2).into(),
-            _ => Pv::invalid(),
-        }
+            (v1, v2) => pv_number_op!(v1, v2, Sub sub, "subtracted"),
+        })
     }
 }
 
@@ -82,10 +286,14 @@ impl std::ops::Mul<&Pv> for Pv {
     type Output = Self;
 
     fn mul(self, other: &Pv) -> Self {
-        match (self, other) {
+        memory_guard(|| match (self, other) {
             (Pv::Int(v1), Pv::Int(v2)) => (v1 * v2).into(),
-            _ => Pv::invalid(),
-        }
+            (Pv::Object(mut v1), Pv::Object(v2)) => {
+                v1.merge_recursive(v2);
+                v1.into()
+            },
+            (v1, v2) => pv_number_op!(v1, v2, Mul mul, "multiplied"),
+        })
     }
 }
 
@@ -93,10 +301,15 @@ impl std::ops::Div<&Pv> for Pv {
     type Output = Self;
 
     fn div(self, other: &Pv) -> Self {
-        match (self, other) {
-            (Pv::Int(v1), Pv::Int(v2)) => (v1 / v2).into(),
-            _ => Pv::invalid(),
-        }
+        memory_guard(|| match (self, other) {
+            // like jq, dividing any number by zero is an error (and not infinity)
+            (v1, v2) if number_to_f64(&v1).is_some() && number_to_f64(v2) == Some(0.0) => op_error(&v1, v2, "divided because the divisor is zero"),
+            // like jq, 1 / 2 is 0.5 and not 0
+            (Pv::Int(v1), Pv::Int(v2)) if v1.checked_rem(v2) == Some(PvInt::new(0)) => v1.checked_div(v2).into(),
+            // dividing strings splits them, like jq
+            (Pv::String(v1), Pv::String(v2)) => v1.split(v2).into(),
+            (v1, v2) => pv_number_op!(v1, v2, Div div, "divided"),
+        })
     }
 }
 
@@ -104,10 +317,44 @@ impl std::ops::Rem<&Pv> for Pv {
     type Output = Self;
 
     fn rem(self, other: &Pv) -> Self {
-        match (self, other) {
-            (Pv::Int(v1), Pv::Int(v2)) => (v1 % v2).into(),
-            _ => Pv::invalid(),
+        // like jq, floats are truncated to ints first
+        fn truncate(value: &Pv) -> Option<PvInt> {
+            match value {
+                Pv::Int(v) => Some(v.clone()),
+                Pv::Float(v) => PvFloat::new(v.value().trunc()).to_int(),
+                _ => None,
+            }
         }
+
+        memory_guard(|| match (self, other) {
+            // zero divisors give an invalid
+            (Pv::Int(v1), Pv::Int(v2)) => v1 % v2,
+            (v1, v2) => match (number_to_f64(&v1), number_to_f64(v2)) {
+                (Some(f1), Some(f2)) if f1.is_nan() || f2.is_nan() => Pv::float(f64::NAN),
+                (Some(_), Some(_)) => match (truncate(&v1), truncate(v2)) {
+                    (Some(_), Some(i2)) if i2 == PvInt::new(0) => op_error(&v1, v2, "divided because the divisor is zero"),
+                    (Some(i1), Some(i2)) => i1 % &i2,
+                    _ => op_error(&v1, v2, "divided"),
+                },
+                _ => op_error(&v1, v2, "divided"),
+            },
+        })
+    }
+}
+
+impl std::ops::Neg for Pv {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        memory_guard(|| match self {
+            // 0 - isize::MIN doesn't fit, so let PvInt promote it
+            Pv::Int(v) => (PvInt::new(0) - &v).into(),
+            Pv::Float(v) => Pv::float(-v.value()),
+            v => {
+                let msg = format!("{} ({}) cannot be negated", v.kind_name(), v.error_text());
+                Pv::invalid_with_msg(msg.as_str().into())
+            },
+        })
     }
 }
 
@@ -131,16 +378,32 @@ macro_rules! pvfromtrans {
     }
 }
 
+// these allocate, so they give an invalid if that would go over this thread's memory limit
+macro_rules! pvfromalloc {
+    ($type:ty, $intertype:ty) => {
+        impl From<$type> for Pv {
+            fn from(value: $type) -> Self {
+                memory_guard(|| <$type as Into<$intertype>>::into(value).into())
+            }
+        }
+    }
+}
+
 pvfrom!(Invalid, PvInvalid);
 pvfrom!(Null, PvNull);
 pvfrom!(Bool, PvBool);
 pvfromtrans!(bool, PvBool);
 pvfrom!(Int, PvInt);
 pvfromtrans!(isize, PvInt);
+pvfrom!(Float, PvFloat);
+pvfromtrans!(f64, PvFloat);
 pvfrom!(String, PvString);
-pvfromtrans!(&str, PvString);
+pvfromalloc!(&str, PvString);
+pvfrom!(Bytes, PvBytes);
+pvfromalloc!(&[u8], PvBytes);
 pvfrom!(Array, PvArray);
-pvfromtrans!(&[Pv], PvArray);
+pvfromalloc!(&[Pv], PvArray);
+pvfrom!(Object, PvObject);
 
 macro_rules! unref_op_impl {
     ($type1:ident $type2:ident $optrait:ident $op:ident) => {
@@ -170,6 +433,8 @@ unref_op_impl!(PvInt PvInt Rem rem);
 
 unref_op_impl!(PvString PvString Add add);
 
+unref_op_impl!(PvBytes PvBytes Add add);
+
 unref_op_impl!(PvArray PvArray Add add);
 
 #[cfg(test)]
@@ -205,7 +470,7 @@ mod tests {
 
     #[test]
     fn test_add_invalid() {
-        assert_eq!(Pv::int(15) + Pv::bool(true), Pv::invalid());
+        assert_eq!(Pv::int(15) + Pv::bool(true), Pv::invalid_with_msg("int (15) and boolean (true) cannot be added".into()));
     }
 
     #[test]
@@ -284,19 +549,25 @@ mod tests {
 
     #[test]
     fn test_array_concat_empty() {
-        assert_eq!(PvArray::new(&["string".into()]).concat(&PvArray::new(&[])), PvArray::new(&["string".into()]));
+        let mut a = PvArray::new(&["string".into()]);
+        a.concat(&PvArray::new(&[]));
+        assert_eq!(a, PvArray::new(&["string".into()]));
     }
 
     #[test]
     fn test_array_concat() {
-        assert_eq!(PvArray::new(&["string".into()]).concat(&PvArray::new(&["STRING".into()])), PvArray::new(&["string".into(), "STRING".into()]));
+        let mut a = PvArray::new(&["string".into()]);
+        a.concat(&PvArray::new(&["STRING".into()]));
+        assert_eq!(a, PvArray::new(&["string".into(), "STRING".into()]));
     }
 
     #[test]
     fn test_array_concat_unchanged() {
         let a = PvArray::new(&["string".into()]);
         let b = PvArray::new(&["STRING".into()]);
-        assert_eq!(a.clone().concat(&b), PvArray::new(&["string".into(), "STRING".into()]));
+        let mut c = a.clone();
+        c.concat(&b);
+        assert_eq!(c, PvArray::new(&["string".into(), "STRING".into()]));
         assert_eq!(a, PvArray::new(&["string".into()]));
         assert_eq!(b, PvArray::new(&["STRING".into()]));
     }
@@ -304,15 +575,969 @@ mod tests {
     // use the PvArray::resize_move() path
     #[test]
     fn test_array_concat2() {
-        assert_eq!(PvArray::new(&["s".into()]).concat(&PvArray::new(&["STRING".into()])), PvArray::new(&["s".into(), "STRING".into()]));
+        let mut a = PvArray::new(&["s".into()]);
+        a.concat(&PvArray::new(&["STRING".into()]));
+        assert_eq!(a, PvArray::new(&["s".into(), "STRING".into()]));
     }
 
     #[test]
     fn test_array_concat_unchanged2() {
         let a = PvArray::new(&["s".into()]);
         let b = PvArray::new(&["STRING".into()]);
-        assert_eq!(a.clone().concat(&b), PvArray::new(&["s".into(), "STRING".into()]));
+        let mut c = a.clone();
+        c.concat(&b);
+        assert_eq!(c, PvArray::new(&["s".into(), "STRING".into()]));
         assert_eq!(a, PvArray::new(&["s".into()]));
         assert_eq!(b, PvArray::new(&["STRING".into()]));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(Pv::from_json("null"), Ok(Pv::null()));
+        assert_eq!(Pv::from_json(" true "), Ok(Pv::bool(true)));
+        assert_eq!(Pv::from_json("false"), Ok(Pv::bool(false)));
+        assert_eq!(Pv::from_json("-42"), Ok(Pv::int(-42)));
+        assert_eq!(Pv::from_json("\"a\\n\\u00e9\\ud83d\\ude00\""), Ok("a\n\u{e9}\u{1f600}".into()));
+    }
+
+    #[test]
+    fn test_parse_nested() {
+        let object = PvObject::new(&[
+            ("a".into(), PvArray::new(&[Pv::int(1), Pv::null(), "x".into()]).into()),
+            ("b".into(), Pv::object()),
+        ]);
+        assert_eq!(Pv::from_json("{\"a\": [1, null, \"x\"], \"b\": {}}"), Ok(object.into()));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = Pv::from_json("[1,\n  2,\n  ]").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        let err = Pv::from_json("\"\u{e9}\u{e9}\" x").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 6));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["", "nul", "[1,]", "{\"a\" 1}", "{1: 2}", "01", "1.", "\"\\x\"", "\"\t\"", "[] []"] {
+            assert!(Pv::from_json(text).is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        assert!(Pv::from_json(&"[".repeat(100000)).is_err());
+    }
+
+    #[test]
+    fn test_print_compact() {
+        let value = Pv::from_json(" [1, \"a\", [], {}, [null, true, false]] ").unwrap();
+        assert_eq!(value.to_json(), "[1,\"a\",[],{},[null,true,false]]");
+        assert_eq!(Pv::from_json("{\"k\": [2]}").unwrap().to_json(), "{\"k\":[2]}");
+    }
+
+    #[test]
+    fn test_print_indent() {
+        let value = Pv::from_json("[1, {\"k\": [2]}]").unwrap();
+        assert_eq!(value.to_json_with(&JsonOptions::pretty()), "[\n  1,\n  {\n    \"k\": [\n      2\n    ]\n  }\n]");
+        let opts = JsonOptions {indent: JsonIndent::Tab, ..JsonOptions::compact()};
+        assert_eq!(value.to_json_with(&opts), "[\n\t1,\n\t{\n\t\t\"k\": [\n\t\t\t2\n\t\t]\n\t}\n]");
+    }
+
+    #[test]
+    fn test_print_colors() {
+        let colors = JsonColors::parse("1;30:31:32:33:34:35:36:37;1").unwrap();
+        let opts = JsonOptions {colors: Some(colors), ..JsonOptions::compact()};
+        let value = Pv::from_json("[null,false,true,1,\"s\",{\"k\":[]}]").unwrap();
+        assert_eq!(
+            value.to_json_with(&opts),
+            "\x1b[35m[\x1b[1;30mnull\x1b[0m\x1b[35m,\x1b[31mfalse\x1b[0m\x1b[35m,\x1b[32mtrue\x1b[0m\x1b[35m,\x1b[33m1\x1b[0m\x1b[35m,\x1b[34m\"s\"\x1b[0m\x1b[35m,\x1b[36m{\x1b[37;1m\"k\"\x1b[0m\x1b[36m:\x1b[35m[]\x1b[0m\x1b[36m}\x1b[0m\x1b[35m]\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        let colors = JsonColors::parse("4;31").unwrap();
+        assert_eq!(colors.null.as_str(), "4;31");
+        assert_eq!(colors.key, JsonColors::default().key);
+        assert_eq!(JsonColors::default().null.as_str(), "0;90");
+        assert!(JsonColors::parse("1;31:red").is_none());
+        assert!(JsonColors::parse("1;31;1;31;1;31").is_none());
+        assert!(JsonColors::parse("1:2:3:4:5:6:7:8:9").is_some());
+        // a file is never a terminal
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+        assert_eq!(JsonOptions::pretty().colored_for(&file).colors, None);
+    }
+
+    #[test]
+    fn test_print_escapes() {
+        let value: Pv = "\"\\\n\u{1}\u{e9}\u{1f600}".into();
+        assert_eq!(value.to_json(), "\"\\\"\\\\\\n\\u0001\u{e9}\u{1f600}\"");
+        let opts = JsonOptions {ascii: true, ..JsonOptions::compact()};
+        assert_eq!(value.to_json_with(&opts), "\"\\\"\\\\\\n\\u0001\\u00e9\\ud83d\\ude00\"");
+    }
+
+    #[test]
+    fn test_print_roundtrip() {
+        let text = "{\"a\":[1,-2,\"\\u0000x\"],\"b\":null}";
+        let value = Pv::from_json(text).unwrap();
+        assert_eq!(Pv::from_json(&value.to_json()), Ok(value));
+    }
+
+    #[test]
+    fn test_float() {
+        assert_eq!(Pv::float(1.5), Pv::Float(PvFloat::new(1.5)));
+    }
+
+    #[test]
+    fn test_int_float_eq() {
+        assert_eq!(Pv::int(3), Pv::float(3.0));
+        assert_ne!(Pv::int(3), Pv::float(3.5));
+        let set: std::collections::HashSet<Pv> = [Pv::int(3), Pv::float(3.0), Pv::float(-0.0), Pv::int(0)].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_mixed_arithmetic() {
+        assert_eq!(Pv::int(1) + Pv::float(0.5), Pv::float(1.5));
+        assert_eq!(Pv::float(0.5) * Pv::int(3), Pv::float(1.5));
+        assert_eq!(Pv::float(2.5) - Pv::float(0.5), Pv::int(2));
+        assert_eq!(Pv::int(1) / Pv::int(2), Pv::float(0.5));
+        assert_eq!(Pv::int(6) / Pv::int(2), Pv::Int(PvInt::new(3)));
+        assert_eq!(Pv::float(7.9) % Pv::int(3), Pv::int(1));
+        assert!(!(Pv::float(1.5) + Pv::from("a")).is_valid());
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(Pv::from_json("1.5"), Ok(Pv::float(1.5)));
+        assert_eq!(Pv::from_json("-2.5e-3"), Ok(Pv::float(-0.0025)));
+        assert_eq!(Pv::from_json("1e300"), Ok(Pv::float(1e300)));
+        assert_eq!(Pv::from_json("1e20"), Ok(Pv::float(1e20)));
+    }
+
+    #[test]
+    fn test_print_float() {
+        assert_eq!(Pv::float(1.5).to_json(), "1.5");
+        assert_eq!(Pv::float(3.0).to_json(), "3");
+        assert_eq!(Pv::float(0.1).to_json(), "0.1");
+        assert_eq!(Pv::float(1e300).to_json(), "1e+300");
+        assert_eq!(Pv::float(-2.5e-7).to_json(), "-2.5e-7");
+        assert_eq!(Pv::float(f64::NAN).to_json(), "null");
+        assert_eq!(Pv::float(f64::INFINITY).to_json(), "1.7976931348623157e+308");
+    }
+
+    #[test]
+    fn test_int_no_overflow() {
+        let big = Pv::int(isize::MAX) * Pv::int(isize::MAX) * Pv::int(4);
+        assert_eq!(big.to_json(), (PvInt::new(isize::MAX) * &PvInt::new(isize::MAX) * &PvInt::new(4)).to_string());
+        assert_eq!(big.clone() / Pv::int(isize::MAX) / Pv::int(isize::MAX), Pv::int(4));
+        assert_eq!(big - Pv::int(1) + Pv::int(1), Pv::Int(PvInt::new(isize::MAX) * &PvInt::new(isize::MAX) * &PvInt::new(4)));
+    }
+
+    #[test]
+    fn test_parse_bigint() {
+        let text = "[123456789012345678901234567890,-98765432109876543210]";
+        let value = Pv::from_json(text).unwrap();
+        assert_eq!(value.to_json(), text);
+        assert_eq!(Pv::from_json("100000000000000000000"), Ok(Pv::float(1e20)));
+        // these are kept exactly up to a point, and past that they're floats (so infinite)
+        let long = "9".repeat(4096);
+        assert_eq!(Pv::from_json(&long).unwrap().to_json(), long);
+        assert_eq!(Pv::from_json(&format!("-{}", long)).unwrap().to_json(), format!("-{}", long));
+        assert_eq!(Pv::from_json(&format!("{}9", long)), Ok(Pv::float(f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!(Pv::bytes(b"ab"), Pv::Bytes(PvBytes::new(b"ab")));
+        assert_eq!(Pv::bytes(b"ab") + Pv::bytes(b"cd"), Pv::from(&b"abcd"[..]));
+        assert_ne!(Pv::bytes(b"ab"), "ab".into());
+    }
+
+    #[test]
+    fn test_bytes_concat_unchanged() {
+        let a = PvBytes::new(b"s");
+        let b = PvBytes::new(b"STRING");
+        assert_eq!(a.clone() + b.clone(), PvBytes::new(b"sSTRING"));
+        assert_eq!(a, PvBytes::new(b"s"));
+        assert_eq!(b, PvBytes::new(b"STRING"));
+    }
+
+    #[test]
+    fn test_bytes_index() {
+        let bytes = PvBytes::new(b"abc");
+        assert_eq!(bytes[1], b'b');
+        assert_eq!(bytes.get(-1), Some(b'c'));
+        assert_eq!(bytes.get(3), None);
+        assert_eq!(bytes.get(-4), None);
+    }
+
+    #[test]
+    fn test_bytes_string_conversion() {
+        let bytes = PvBytes::from(&PvString::new("h\u{e9}"));
+        assert_eq!(bytes.as_slice(), "h\u{e9}".as_bytes());
+        assert_eq!(bytes.to_utf8(), Ok(PvString::new("h\u{e9}")));
+        assert!(PvBytes::new(b"a\xff").to_utf8().is_err());
+        assert_eq!(PvBytes::new(b"a\xff").to_utf8_lossy(), PvString::new("a\u{fffd}"));
+    }
+
+    #[test]
+    fn test_bytes_text_encodings() {
+        let bytes = PvBytes::new(b"\x00\xffhello");
+        assert_eq!(bytes.to_hex(), PvString::new("00ff68656c6c6f"));
+        assert_eq!(PvBytes::from_hex("00FF68656c6c6f"), Some(bytes.clone()));
+        assert_eq!(PvBytes::from_hex("0"), None);
+        for (raw, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg==")] {
+            assert_eq!(PvBytes::new(raw.as_bytes()).to_base64(), PvString::new(encoded));
+            assert_eq!(PvBytes::from_base64(encoded), Some(PvBytes::new(raw.as_bytes())));
+        }
+        assert_eq!(PvBytes::from_base64("Zm9vYg"), Some(PvBytes::new(b"foob")));
+        assert_eq!(PvBytes::from_base64("Zm9v!g=="), None);
+        assert_eq!(PvBytes::from_base64(bytes.to_base64().get_str()), Some(bytes));
+    }
+
+    #[test]
+    fn test_print_bytes() {
+        assert_eq!(Pv::bytes(b"\x01\xff").to_json(), "[1,255]");
+    }
+
+    fn hash_of(value: &Pv) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_object_hash() {
+        let a = Pv::from_json("{\"a\": 1, \"b\": [2, {\"c\": null}], \"d\": \"e\"}").unwrap();
+        let b = Pv::from_json("{\"d\": \"e\", \"b\": [2, {\"c\": null}], \"a\": 1.0}").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_ne!(hash_of(&a), hash_of(&Pv::from_json("{\"a\": 2}").unwrap()));
+        assert_ne!(hash_of(&Pv::object()), hash_of(&Pv::from_json("{\"a\": 2}").unwrap()));
+    }
+
+    #[test]
+    fn test_object_as_key() {
+        let objects = ["{}", "{\"a\": 1}", "{\"a\": 1}", "[{\"a\": 1}]", "[{\"a\": 1}]", "{\"a\": {}}"];
+        let set: std::collections::HashSet<Pv> = objects.iter().map(|text| Pv::from_json(text).unwrap()).collect();
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    fn test_object_insertion_order() {
+        let text = "{\"z\":1,\"a\":2,\"m\":{\"y\":3,\"b\":4}}";
+        assert_eq!(Pv::from_json(text).unwrap().to_json(), text);
+        // a repeated key keeps its first position
+        assert_eq!(Pv::from_json("{\"b\":1,\"a\":2,\"b\":3}").unwrap().to_json(), "{\"b\":3,\"a\":2}");
+    }
+
+    #[test]
+    fn test_object_order_independent_eq() {
+        assert_eq!(Pv::from_json("{\"a\":1,\"b\":2}"), Pv::from_json("{\"b\":2,\"a\":1}"));
+        assert_ne!(Pv::from_json("{\"a\":1,\"b\":2}"), Pv::from_json("{\"a\":1}"));
+    }
+
+    #[test]
+    fn test_print_sort_keys() {
+        let value = Pv::from_json("{\"z\":1,\"a\":2,\"m\":{\"y\":3,\"b\":4}}").unwrap();
+        let opts = JsonOptions {sort_keys: true, ..JsonOptions::compact()};
+        assert_eq!(value.to_json_with(&opts), "{\"a\":2,\"m\":{\"b\":4,\"y\":3},\"z\":1}");
+    }
+
+    #[test]
+    fn test_object_accessors() {
+        let object = PvObject::new(&[("a".into(), Pv::int(1)), ("b".into(), Pv::null())]);
+        assert_eq!(object.len(), 2);
+        assert_eq!(object.get(&"a".into()), Some(Pv::int(1)));
+        assert_eq!(object.get(&"c".into()), None);
+        assert!(object.contains_key(&"b".into()));
+        assert!(!object.contains_key(&"c".into()));
+        assert_eq!(object.keys().cloned().collect::<Vec<_>>(), vec!["a".into(), "b".into()]);
+        assert_eq!(object.values().cloned().collect::<Vec<_>>(), vec![Pv::int(1), Pv::null()]);
+        assert_eq!(object.iter().count(), 2);
+        assert!(PvObject::new_empty().is_empty());
+    }
+
+    #[test]
+    fn test_object_insert_remove() {
+        let mut object = PvObject::new_empty();
+        object.insert("a".into(), Pv::int(1));
+        object.insert("b".into(), Pv::int(2));
+        object.insert("c".into(), Pv::int(3));
+        object.insert("a".into(), Pv::int(4));
+        assert_eq!(object.remove(&"b".into()), Some(Pv::int(2)));
+        assert_eq!(object.remove(&"x".into()), None);
+        assert_eq!(Pv::from(object.clone()).to_json(), "{\"a\":4,\"c\":3}");
+        assert_eq!(object.get(&"c".into()), Some(Pv::int(3)));
+    }
+
+    #[test]
+    fn test_object_insert_unchanged() {
+        let a = PvObject::new(&[("a".into(), Pv::int(1))]);
+        let mut b = a.clone();
+        b.insert("b".into(), Pv::int(2));
+        let mut c = a.clone();
+        c.remove(&"a".into());
+        assert_eq!(a, PvObject::new(&[("a".into(), Pv::int(1))]));
+        assert_eq!(b.len(), 2);
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_object_merge() {
+        let a = Pv::from_json("{\"a\":{\"x\":1,\"y\":2},\"b\":1}").unwrap();
+        let b = Pv::from_json("{\"a\":{\"y\":3,\"z\":4},\"c\":5}").unwrap();
+        assert_eq!((a.clone() + &b).to_json(), "{\"a\":{\"y\":3,\"z\":4},\"b\":1,\"c\":5}");
+        assert_eq!((a.clone() * &b).to_json(), "{\"a\":{\"x\":1,\"y\":3,\"z\":4},\"b\":1,\"c\":5}");
+        assert_eq!(a, Pv::from_json("{\"a\":{\"x\":1,\"y\":2},\"b\":1}").unwrap());
+    }
+
+    fn int_array(values: &[isize]) -> PvArray {
+        values.iter().map(|&v| Pv::int(v)).collect::<Vec<_>>().as_slice().into()
+    }
+
+    #[test]
+    fn test_array_get() {
+        let array = int_array(&[1, 2, 3]);
+        assert_eq!(array.len(), 3);
+        assert_eq!(array.get(0), Some(Pv::int(1)));
+        assert_eq!(array.get(-1), Some(Pv::int(3)));
+        assert_eq!(array.get(3), None);
+        assert_eq!(array.get(-4), None);
+        assert_eq!(array.iter().cloned().collect::<Vec<_>>(), vec![Pv::int(1), Pv::int(2), Pv::int(3)]);
+    }
+
+    #[test]
+    fn test_array_set() {
+        let mut array = int_array(&[1, 2]);
+        assert!(array.set(-1, Pv::int(5)));
+        assert!(array.set(3, Pv::int(6)));
+        assert!(!array.set(-6, Pv::int(7)));
+        assert!(!array.set(PvArray::MAX_INDEX as isize + 1, Pv::int(8)));
+        assert_eq!(array, PvArray::new(&[Pv::int(1), Pv::int(5), Pv::null(), Pv::int(6)]));
+    }
+
+    #[test]
+    fn test_array_insert_remove() {
+        let mut array = int_array(&[1, 2, 3]);
+        array.insert(1, Pv::int(9));
+        array.insert(-1, Pv::int(8));
+        array.insert(100, Pv::int(7));
+        assert_eq!(array, int_array(&[1, 9, 2, 8, 3, 7]));
+        assert_eq!(array.remove(0), Some(Pv::int(1)));
+        assert_eq!(array.remove(-2), Some(Pv::int(3)));
+        assert_eq!(array.remove(4), None);
+        assert_eq!(array, int_array(&[9, 2, 8, 7]));
+    }
+
+    #[test]
+    fn test_array_slice() {
+        let array = int_array(&[0, 1, 2, 3, 4]);
+        assert_eq!(array.slice(1, 3), int_array(&[1, 2]));
+        assert_eq!(array.slice(-2, 100), int_array(&[3, 4]));
+        assert_eq!(array.slice(-100, 1), int_array(&[0]));
+        assert_eq!(array.slice(3, 1), PvArray::new_empty());
+    }
+
+    #[test]
+    fn test_array_reverse_truncate() {
+        let mut array = int_array(&[1, 2, 3, 4]);
+        array.reverse();
+        assert_eq!(array, int_array(&[4, 3, 2, 1]));
+        array.truncate(2);
+        assert_eq!(array, int_array(&[4, 3]));
+        array.truncate(5);
+        assert_eq!(array, int_array(&[4, 3]));
+    }
+
+    #[test]
+    fn test_array_edit_unchanged() {
+        let a = int_array(&[1, 2, 3]);
+        let mut b = a.clone();
+        b.set(0, Pv::int(5));
+        let mut c = a.clone();
+        c.insert(0, Pv::int(5));
+        let mut d = a.clone();
+        d.remove(0);
+        let mut e = a.clone();
+        e.reverse();
+        let mut f = a.clone();
+        f.truncate(0);
+        assert_eq!(a, int_array(&[1, 2, 3]));
+        assert_eq!((b.len(), c.len(), d.len(), e.len(), f.len()), (3, 4, 2, 3, 0));
+    }
+
+    fn string_array(values: &[&str]) -> PvArray {
+        values.iter().map(|&v| v.into()).collect::<Vec<Pv>>().as_slice().into()
+    }
+
+    #[test]
+    fn test_string_lengths() {
+        let s = PvString::new("h\u{e9}llo");
+        assert_eq!(s.as_str(), "h\u{e9}llo");
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.char_len(), 5);
+        assert!(PvString::new_empty().is_empty());
+    }
+
+    #[test]
+    fn test_string_slice() {
+        let s = PvString::new("h\u{e9}llo");
+        assert_eq!(s.slice(1, 3), PvString::new("\u{e9}l"));
+        assert_eq!(s.slice(-2, 100), PvString::new("lo"));
+        assert_eq!(s.slice(3, 1), PvString::new(""));
+    }
+
+    #[test]
+    fn test_string_split_join() {
+        let s = PvString::new("a, b, c");
+        assert_eq!(s.split(&", ".into()), string_array(&["a", "b", "c"]));
+        assert_eq!(PvString::new("ab").split(&"".into()), string_array(&["a", "b"]));
+        assert_eq!(PvString::new("").split(&",".into()), PvArray::new_empty());
+        assert_eq!(Pv::from("a,b") / Pv::from(","), string_array(&["a", "b"]).into());
+        let parts = PvArray::new(&["a".into(), Pv::null(), Pv::int(1), Pv::bool(true)]);
+        assert_eq!(PvString::new("-").join(&parts), Some(PvString::new("a--1-true")));
+        assert_eq!(PvString::new("-").join(&PvArray::new(&[Pv::array()])), None);
+    }
+
+    #[test]
+    fn test_string_affixes() {
+        let s = PvString::new("prefix-body-suffix");
+        assert!(s.starts_with(&"prefix".into()));
+        assert!(!s.starts_with(&"body".into()));
+        assert!(s.ends_with(&"suffix".into()));
+        assert_eq!(s.clone().ltrimstr(&"prefix-".into()), PvString::new("body-suffix"));
+        assert_eq!(s.clone().ltrimstr(&"body".into()), s);
+        assert_eq!(s.clone().rtrimstr(&"-suffix".into()), PvString::new("prefix-body"));
+    }
+
+    #[test]
+    fn test_string_trim_case() {
+        let s = PvString::new(" \t h\u{c9}y \n");
+        assert_eq!(s.trim(), PvString::new("h\u{c9}y"));
+        assert_eq!(s.ltrim(), PvString::new("h\u{c9}y \n"));
+        assert_eq!(s.rtrim(), PvString::new(" \t h\u{c9}y"));
+        assert_eq!(s.trim().ascii_downcase(), PvString::new("h\u{c9}y"));
+        assert_eq!(s.trim().ascii_upcase(), PvString::new("H\u{c9}Y"));
+    }
+
+    #[test]
+    fn test_string_explode_implode() {
+        let s = PvString::new("a\u{e9}\u{1f600}");
+        let codepoints = int_array(&[97, 0xe9, 0x1f600]);
+        assert_eq!(s.explode(), codepoints);
+        assert_eq!(PvString::implode(&codepoints), Some(s));
+        assert_eq!(PvString::implode(&int_array(&[0xd800])), None);
+        assert_eq!(PvString::implode(&PvArray::new(&["a".into()])), None);
+    }
+
+    #[test]
+    fn test_string_indices() {
+        let s = PvString::new("\u{e9}a,b, cd, efg");
+        assert_eq!(s.indices(&", ".into()), int_array(&[4, 8]));
+        assert_eq!(PvString::new("aaa").indices(&"aa".into()), int_array(&[0, 1]));
+        assert_eq!(s.indices(&"".into()), PvArray::new_empty());
+    }
+
+    #[test]
+    fn test_invalid_with_msg() {
+        let invalid = Pv::invalid_with_msg("oops".into());
+        assert!(!invalid.is_valid());
+        assert!(Pv::null().is_valid());
+        assert_ne!(invalid, Pv::invalid());
+        assert_eq!(invalid.to_json(), "<invalid:\"oops\">");
+        assert_eq!(Pv::invalid().to_json(), "<invalid>");
+    }
+
+    fn error_msg(value: Pv) -> Option<Pv> {
+        match value {
+            Pv::Invalid(v) => v.msg(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_op_error_messages() {
+        assert_eq!(error_msg(Pv::null() - Pv::from("a")), Some("null (null) and string (\"a\") cannot be subtracted".into()));
+        assert_eq!(error_msg(Pv::float(1.5) * Pv::array()), Some("float (1.5) and array ([]) cannot be multiplied".into()));
+        assert_eq!(error_msg(Pv::object() / Pv::int(2)), Some("object ({}) and int (2) cannot be divided".into()));
+        assert_eq!(error_msg(Pv::float(5.0) % Pv::float(0.5)), Some("float (5) and float (0.5) cannot be divided because the divisor is zero".into()));
+        let long = Pv::from("a very long string");
+        assert_eq!(error_msg(long + Pv::int(1)), Some("string (\"a very lon...) and int (1) cannot be added".into()));
+    }
+
+    #[test]
+    fn test_kind_order() {
+        let sorted = ["null", "false", "true", "-1", "0.5", "1", "\"\"", "\"a\"", "[]", "[0]", "{}"];
+        let values: Vec<Pv> = sorted.iter().map(|text| Pv::from_json(text).unwrap()).collect();
+        for (i, v1) in values.iter().enumerate() {
+            for (j, v2) in values.iter().enumerate() {
+                assert_eq!(v1.cmp(v2), i.cmp(&j), "{} vs {}", v1, v2);
+            }
+        }
+        assert!(Pv::invalid() < Pv::null());
+        assert!(Pv::from("z") < Pv::bytes(b"") && Pv::bytes(b"") < Pv::array());
+    }
+
+    #[test]
+    fn test_number_order() {
+        assert_eq!(Pv::int(1).cmp(&Pv::float(1.0)), std::cmp::Ordering::Equal);
+        assert!(Pv::float(f64::NAN) < Pv::int(isize::MIN));
+        assert!(Pv::int(2) < Pv::float(2.5));
+        assert!(Pv::float(1e300) > Pv::int(isize::MAX) * Pv::int(isize::MAX));
+    }
+
+    #[test]
+    fn test_array_order() {
+        let parse = |text| Pv::from_json(text).unwrap();
+        assert!(parse("[1, 2]") < parse("[1, 3]"));
+        assert!(parse("[1, 2]") < parse("[1, 2, 0]"));
+        assert!(parse("[2]") > parse("[1, 2, 0]"));
+    }
+
+    #[test]
+    fn test_object_order() {
+        let parse = |text| Pv::from_json(text).unwrap();
+        // keys are compared first
+        assert!(parse("{\"a\": 2}") < parse("{\"b\": 1}"));
+        assert!(parse("{\"a\": 2}") < parse("{\"a\": 1, \"b\": 1}"));
+        // then values in key order
+        assert!(parse("{\"b\": 1, \"a\": 1}") < parse("{\"a\": 1, \"b\": 2}"));
+        assert_eq!(parse("{\"b\": 1, \"a\": 1}").cmp(&parse("{\"a\": 1, \"b\": 1}")), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_array_sort() {
+        let mut array = Pv::from_json("[{}, [1], \"b\", 3, null, 1.5, true, \"a\", false, []]").unwrap();
+        let Pv::Array(ref mut array) = array else {unreachable!()};
+        let unsorted = array.clone();
+        assert_eq!(array.min_value(), Some(Pv::null()));
+        assert_eq!(array.max_value(), Some(Pv::object()));
+        array.sort();
+        assert_eq!(Pv::from(array.clone()).to_json(), "[null,false,true,1.5,3,\"a\",\"b\",[],[1],{}]");
+        assert_eq!(unsorted.len(), 10);
+        assert_ne!(&unsorted, array);
+        assert_eq!(PvArray::new_empty().min_value(), None);
+    }
+
+    #[test]
+    fn test_array_slice_shares() {
+        let array = int_array(&[0, 1, 2, 3, 4]);
+        let mut slice = array.slice(1, 4);
+        assert_eq!(slice.as_slice().as_ptr(), array.as_slice()[1..].as_ptr());
+        slice.set(0, Pv::int(9));
+        slice.append(Pv::int(8));
+        assert_eq!(slice, int_array(&[9, 2, 3, 8]));
+        assert_eq!(array, int_array(&[0, 1, 2, 3, 4]));
+        // a slice that outlives what it was sliced from
+        let mut slice = array.slice(2, 5);
+        drop(array);
+        assert_eq!(slice, int_array(&[2, 3, 4]));
+        slice.append(Pv::int(5));
+        assert_eq!(slice, int_array(&[2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn test_string_slice_shares() {
+        let s = PvString::new(" the first long piece of text, and the second long piece ");
+        let trimmed = s.trim();
+        assert_eq!(trimmed.as_str().as_ptr(), s.as_str()[1..].as_ptr());
+        let slice = trimmed.slice(4, 30);
+        assert_eq!(slice.as_str().as_ptr(), s.as_str()[5..].as_ptr());
+        let parts = s.split(&PvString::new(","));
+        let Some(Pv::String(part)) = parts.get(1) else {unreachable!()};
+        assert_eq!(part.as_str().as_ptr(), s.as_str()[30..].as_ptr());
+        let slice = slice + &PvString::new("!");
+        assert_eq!(slice.as_str(), "first long piece of text, !");
+        assert_eq!(trimmed.as_str(), "the first long piece of text, and the second long piece");
+        assert_eq!(s.as_str(), " the first long piece of text, and the second long piece ");
+    }
+
+    #[test]
+    fn test_string_inline() {
+        let s = PvString::new("key");
+        assert!(s.is_inline());
+        // short slices of long strings are copied instead of keeping the whole thing alive
+        let long = PvString::new("a string that is too long to be inline");
+        assert!(!long.is_inline());
+        assert!(long.slice(0, 8).is_inline());
+        assert_eq!(long.slice(0, 8), PvString::new("a string"));
+        // growing past the inline size moves it to the heap
+        let grown = s.clone() + &PvString::new(" that keeps going and going");
+        assert!(!grown.is_inline());
+        assert_eq!(grown.as_str(), "key that keeps going and going");
+        assert_eq!(s.as_str(), "key");
+        assert_eq!(hash_of(&Pv::from(grown.slice(0, 3))), hash_of(&Pv::from(s)));
+    }
+
+    #[test]
+    fn test_string_intern() {
+        let a = PvString::intern("an object key that gets repeated");
+        let b = PvString::intern("an object key that gets repeated");
+        let c = PvString::new("an object key that gets repeated");
+        assert_eq!(a.as_str().as_ptr(), b.as_str().as_ptr());
+        assert_ne!(a.as_str().as_ptr(), c.as_str().as_ptr());
+        assert_eq!(a, b);
+        assert_eq!(a, c);
+        assert_eq!(hash_of(&Pv::from(a.clone())), hash_of(&Pv::from(c)));
+        PvString::clear_interned();
+        let d = PvString::intern("an object key that gets repeated");
+        assert_ne!(a.as_str().as_ptr(), d.as_str().as_ptr());
+        assert_eq!(a, d);
+    }
+
+    #[test]
+    fn test_object_keys_interned() {
+        fn key_ptr(object: &Pv) -> *const u8 {
+            let Pv::Object(object) = object else {unreachable!()};
+            let Some(Pv::String(key)) = object.keys().next() else {unreachable!()};
+            key.as_str().as_ptr()
+        }
+
+        let parsed = Pv::from_json(r#"[{"a key too long to be stored inline": 1}, {"a key too long to be stored inline": 2}]"#).unwrap();
+        let Pv::Array(parsed) = parsed else {unreachable!()};
+        assert_eq!(key_ptr(&parsed.as_slice()[0]), key_ptr(&parsed.as_slice()[1]));
+
+        // only when asked for, so nothing builds up in the table on its own
+        let mut a = PvObject::new_empty();
+        a.insert(PvString::new("another key too long to be stored inline").into(), Pv::int(1));
+        let b = PvObject::new(&[(PvString::new("another key too long to be stored inline").into(), Pv::int(2))]);
+        assert_ne!(key_ptr(&a.clone().into()), key_ptr(&b.into()));
+        let c = PvObject::new(&[(PvString::intern("another key too long to be stored inline").into(), Pv::int(2))]);
+        let d = PvObject::new(&[(PvString::new("another key too long to be stored inline").into_interned().into(), Pv::int(2))]);
+        assert_eq!(key_ptr(&c.into()), key_ptr(&d.into()));
+
+        // a slice gets its own copy instead of keeping the whole string alive
+        let long = PvString::new("a key that is a slice of a much longer string than the key itself");
+        let sliced = long.slice(0, 40).into_interned();
+        assert_ne!(sliced.as_str().as_ptr(), long.as_str().as_ptr());
+        assert_eq!(sliced.as_str(), &long.as_str()[..40]);
+        PvString::clear_interned();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let text = r#"{"a":[1,-2.5,"x",null,true],"b":{"c":{}},"d":18446744073709551615}"#;
+        let value: Pv = serde_json::from_str(text).unwrap();
+        assert_eq!(value, Pv::from_json(text).unwrap());
+        let back = serde_json::to_string(&value).unwrap();
+        assert_eq!(Pv::from_json(&back).unwrap(), value);
+        assert!(serde_json::to_string(&Pv::invalid()).is_err());
+        // big ints are written exactly as long as the format can take them
+        let big = Pv::from_json("-123456789012345678901234567890").unwrap();
+        assert_eq!(serde_json::to_string(&big).unwrap(), "-123456789012345678901234567890");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_variants() {
+        let s: PvString = serde_json::from_str("\"hi\"").unwrap();
+        assert_eq!(s.as_str(), "hi");
+        let bytes: PvBytes = serde_json::from_str("[1,2,255]").unwrap();
+        assert_eq!(bytes.as_slice(), &[1, 2, 255]);
+        assert!(serde_json::from_str::<PvBytes>("[256]").is_err());
+        let f: PvFloat = serde_json::from_str("3").unwrap();
+        assert_eq!(f.value(), 3.0);
+        let err = serde_json::from_str::<PvArray>("{}").unwrap_err();
+        assert!(err.to_string().contains("expected array, got object"));
+        let object: PvObject = serde_json::from_str(r#"{"k":[1]}"#).unwrap();
+        assert_eq!(serde_json::to_string(&object).unwrap(), r#"{"k":[1]}"#);
+    }
+
+    // straight to and from another format's value type, without any text
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_typed() {
+        let value = serde_json::to_value(Pv::from_json(r#"{"name":"x","tags":["a","b"]}"#).unwrap()).unwrap();
+        let back: Pv = serde_json::from_value(value).unwrap();
+        assert_eq!(back.to_json(), r#"{"name":"x","tags":["a","b"]}"#);
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect {w: u32, h: u32},
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+        count: Option<i64>,
+        big: u128,
+        shapes: Vec<Shape>,
+    }
+
+    // straight to and from rust types, with no other format in between
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_from_to_pv() {
+        let value = Pv::from_json(r#"{"name":"x","tags":["a","b"],"count":null,"big":340282366920938463463374607431768211455,"shapes":["Empty",{"Circle":1.5},{"Rect":{"w":2,"h":3}}]}"#).unwrap();
+        let item: Item = from_pv(value.clone()).unwrap();
+        assert_eq!(item, Item {
+            name: "x".into(),
+            tags: vec!["a".into(), "b".into()],
+            count: None,
+            big: u128::MAX,
+            shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect {w: 2, h: 3}],
+        });
+        assert_eq!(to_pv(&item).unwrap(), value);
+
+        assert_eq!(from_pv::<(i32, bool)>(Pv::from_json("[1,true]").unwrap()), Ok((1, true)));
+        assert!(from_pv::<Item>(Pv::from_json(r#"{"name":1}"#).unwrap()).is_err());
+        assert!(from_pv::<u8>(Pv::int(256)).is_err());
+        assert!(from_pv::<Pv>(Pv::invalid()).is_err());
+        // a Pv goes through unchanged either way
+        assert_eq!(from_pv::<Pv>(value.clone()), Ok(value.clone()));
+        assert_eq!(to_pv(&value), Ok(value));
+    }
+
+    // a format can claim any length up front, so that can't decide how much gets allocated
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_size_hint() {
+        use serde::Deserialize;
+        use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+
+        struct Liar<I>(I);
+        impl<I: Iterator> Iterator for Liar<I> {
+            type Item = I::Item;
+            fn next(&mut self) -> Option<I::Item> {
+                self.0.next()
+            }
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (usize::MAX, Some(usize::MAX))
+            }
+        }
+
+        let seq = SeqDeserializer::<_, Error>::new(Liar([1, 2].into_iter()));
+        assert_eq!(Pv::deserialize(seq).unwrap().to_json(), "[1,2]");
+        let map = MapDeserializer::<_, Error>::new(Liar([("a", 1)].into_iter()));
+        assert_eq!(Pv::deserialize(map).unwrap().to_json(), r#"{"a":1}"#);
+    }
+
+    fn path(json: &str) -> PvArray {
+        let Pv::Array(path) = Pv::from_json(json).unwrap() else {unreachable!()};
+        path
+    }
+
+    #[test]
+    fn test_getpath() {
+        let value = Pv::from_json(r#"{"a":[1,{"b":2}],"c":null}"#).unwrap();
+        assert_eq!(value.getpath(&path(r#"["a",1,"b"]"#)), Pv::int(2));
+        assert_eq!(value.getpath(&path(r#"["a",-2]"#)), Pv::int(1));
+        assert_eq!(value.getpath(&path(r#"["c","d",0]"#)), Pv::null());
+        assert_eq!(value.getpath(&path(r#"["x"]"#)), Pv::null());
+        assert_eq!(value.getpath(&path("[]")), value);
+        assert_eq!(error_msg(value.getpath(&path(r#"["a","b"]"#))), Some("array ([1,{\"b\":2}]) cannot be indexed with string (\"b\")".into()));
+    }
+
+    #[test]
+    fn test_setpath() {
+        let value = Pv::from_json(r#"{"a":[1,{"b":2}],"c":[3]}"#).unwrap();
+        let changed = value.clone().setpath(&path(r#"["a",1,"b"]"#), Pv::int(5));
+        assert_eq!(changed.to_json(), r#"{"a":[1,{"b":5}],"c":[3]}"#);
+        assert_eq!(value.to_json(), r#"{"a":[1,{"b":2}],"c":[3]}"#);
+        // the subtree that wasn't on the path is still shared
+        let (Pv::Object(old), Pv::Object(new)) = (&value, &changed) else {unreachable!()};
+        let (Some(Pv::Array(old)), Some(Pv::Array(new))) = (old.get(&"c".into()), new.get(&"c".into())) else {unreachable!()};
+        assert_eq!(old.as_slice().as_ptr(), new.as_slice().as_ptr());
+        assert_eq!(Pv::null().setpath(&path(r#"["x",2]"#), Pv::bool(true)).to_json(), r#"{"x":[null,null,true]}"#);
+        assert_eq!(value.clone().setpath(&path("[]"), Pv::int(1)), Pv::int(1));
+        assert_eq!(error_msg(value.clone().setpath(&path(r#"["a",-5]"#), Pv::int(1))), Some("Out of bounds negative array index".into()));
+        assert_eq!(error_msg(value.clone().setpath(&path(r#"["a",1e15]"#), Pv::int(1))), Some("Array index too large".into()));
+        assert!(!value.setpath(&path(r#"["a",0,"b"]"#), Pv::int(1)).is_valid());
+    }
+
+    #[test]
+    fn test_delpaths() {
+        let value = Pv::from_json(r#"{"a":[0,1,2,3],"b":{"c":1,"d":2},"e":null}"#).unwrap();
+        let paths = |json: &str| path(json).iter().map(|p| path(&p.to_json())).collect::<Vec<PvArray>>();
+        let deleted = value.clone().delpaths(&paths(r#"[["a",1],["a",3],["b","c"],["e","x"],["z"]]"#));
+        assert_eq!(deleted.to_json(), r#"{"a":[0,2],"b":{"d":2},"e":null}"#);
+        assert_eq!(value.clone().delpaths(&paths("[[]]")), Pv::null());
+        assert!(!value.clone().delpaths(&paths(r#"[["a","x"]]"#)).is_valid());
+        // indices are from before anything was deleted, so each element only goes once
+        let array = Pv::from_json("[0,1,2,3]").unwrap();
+        assert_eq!(array.clone().delpaths(&paths("[[1],[1]]")).to_json(), "[0,2,3]");
+        assert_eq!(array.clone().delpaths(&paths("[[3],[-3]]")).to_json(), "[0,2]");
+        assert_eq!(array.clone().delpaths(&paths("[[-1],[3],[10],[-10]]")).to_json(), "[0,1,2]");
+        let nested = Pv::from_json(r#"{"a":[0,1,2,3]}"#).unwrap();
+        assert_eq!(nested.clone().delpaths(&paths(r#"[["a",0],["a",0]]"#)).to_json(), r#"{"a":[1,2,3]}"#);
+        assert_eq!(nested.clone().delpaths(&paths(r#"[["a",-1],["a",1]]"#)).to_json(), r#"{"a":[0,2]}"#);
+        assert_eq!(nested.delpaths(&paths(r#"[["a",1],["a"]]"#)).to_json(), "{}");
+        assert!(!array.delpaths(&paths(r#"[[0],["x"]]"#)).is_valid());
+    }
+
+    #[test]
+    fn test_paths() {
+        let value = Pv::from_json(r#"{"a":[1,{"b":2}],"c":3}"#).unwrap();
+        assert_eq!(Pv::from(value.paths()).to_json(), r#"[["a"],["a",0],["a",1],["a",1,"b"],["c"]]"#);
+        assert_eq!(Pv::int(1).paths(), PvArray::new_empty());
+    }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let msg = |n: &str, d: &str| Some(Pv::from(format!("{} and {} cannot be divided because the divisor is zero", n, d).as_str()));
+        assert_eq!(error_msg(Pv::int(1) / Pv::int(0)), msg("int (1)", "int (0)"));
+        assert_eq!(error_msg(Pv::int(1) % Pv::int(0)), msg("int (1)", "int (0)"));
+        assert_eq!(error_msg(Pv::float(1.5) / Pv::float(-0.0)), msg("float (1.5)", "float (-0)"));
+        assert_eq!(error_msg(Pv::int(1) / Pv::float(0.0)), msg("int (1)", "float (0)"));
+        let big = Pv::int(isize::MAX) * Pv::int(4);
+        assert!(!(big.clone() / Pv::int(0)).is_valid());
+        assert!(!(big % Pv::int(0)).is_valid());
+        assert_eq!(PvInt::new(7).checked_div(&PvInt::new(0)), None);
+        assert_eq!(PvInt::new(7).checked_rem(&PvInt::new(-2)), Some(PvInt::new(1)));
+    }
+
+    #[test]
+    fn test_overflow_promotes() {
+        let min = Pv::int(isize::MIN);
+        assert_eq!((min.clone() / Pv::int(-1)).to_json(), (PvInt::new(isize::MAX) + &PvInt::new(1)).to_string());
+        assert_eq!(min.clone() % Pv::int(-1), Pv::int(0));
+        assert_eq!((min.clone() - Pv::int(1)).to_json(), (PvInt::new(isize::MIN) - &PvInt::new(1)).to_string());
+        assert_eq!(min.clone() - Pv::int(1) + Pv::int(1), min);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Pv>();
+        let value = Pv::from_json(r#"{"a":[1,2,3],"b":"a string that is long enough for the heap"}"#).unwrap();
+        let handles: Vec<_> = (0..8).map(|i| {
+            let value = value.clone();
+            std::thread::spawn(move || {
+                // every thread changes its own copy, which mustn't show up anywhere else
+                let changed = value.clone().setpath(&path(r#"["a",0]"#), Pv::int(i));
+                assert_eq!(value.getpath(&path(r#"["a",0]"#)), Pv::int(1));
+                changed.getpath(&path(r#"["a",0]"#))
+            })
+        }).collect();
+        let results: Vec<Pv> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(results, (0..8).map(Pv::int).collect::<Vec<Pv>>());
+        assert_eq!(value.to_json(), r#"{"a":[1,2,3],"b":"a string that is long enough for the heap"}"#);
+    }
+
+    #[test]
+    fn test_memory_count() {
+        // the count is per thread, so a new one starts at zero and nothing else changes it
+        std::thread::spawn(|| {
+            assert_eq!(memory::allocated_bytes(), 0);
+            let array = PvArray::new(&vec![Pv::null(); 1_000_000]);
+            assert!(memory::allocated_bytes() >= 1_000_000 * std::mem::size_of::<Pv>());
+            drop(array);
+            assert_eq!(memory::allocated_bytes(), 0);
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // on its own thread so the limit can't leak into other tests
+        std::thread::spawn(|| {
+            // made first, since with the limit at 0 even this would be refused
+            let exceeded = Pv::from("memory limit exceeded");
+            let big = Pv::from("x".repeat(200_000).as_str());
+            memory::set_memory_limit(Some(memory::allocated_bytes() + 1_000_000));
+            assert_eq!(memory::memory_limit(), Some(memory::allocated_bytes() + 1_000_000));
+            assert!((big.clone() + &big).is_valid());
+            let huge = Pv::from("x".repeat(300_000).as_str());
+            assert!(huge.is_valid());
+            assert_eq!(error_msg(huge.clone() + &huge), Some(exceeded.clone()));
+            // every way of growing something is refused before it goes over
+            let before = memory::allocated_bytes();
+            assert_eq!(memory::within_limit(|| {
+                let mut array = PvArray::new_empty();
+                for _ in 0..100_000 {
+                    array.append(Pv::null());
+                }
+            }), None);
+            assert_eq!(memory::within_limit(|| PvArray::new(&vec![Pv::null(); 100_000])), None);
+            assert_eq!(memory::within_limit(|| {
+                let mut array = PvArray::new_empty();
+                array.set(100_000, Pv::null())
+            }), None);
+            assert_eq!(memory::allocated_bytes(), before);
+            let key = path("[100000]");
+            assert_eq!(error_msg(Pv::null().setpath(&key, Pv::int(1))), Some(exceeded.clone()));
+            let json = format!("[{}]", vec!["null"; 100_000].join(","));
+            assert_eq!(Pv::from_json(&json).unwrap_err().message, "memory limit exceeded");
+            // small things still fit
+            assert!(Pv::null().setpath(&path("[10]"), Pv::int(1)).is_valid());
+            assert!(Pv::from_json("[1,2,3]").is_ok());
+            let key = path(r#"["a"]"#);
+            // a refusal never turns into a value with something missing
+            // these all need more memory than is left, and they still come out right
+            memory::set_memory_limit(Some(memory::allocated_bytes()));
+            assert_eq!((PvInt::new(isize::MAX) + &PvInt::new(1)).to_string(), "9223372036854775808");
+            assert_ne!(Pv::float(1e20), Pv::int(0));
+            assert_eq!(Pv::float(1e20).cmp(&Pv::int(5)), std::cmp::Ordering::Greater);
+            assert_eq!(PvString::new("x").concat(&PvString::new(&"y".repeat(100))).len(), 101);
+            assert_eq!(PvArray::new(&[Pv::int(1), Pv::int(2)]).len(), 2);
+            // once it's over, everything fails
+            memory::set_memory_limit(Some(0));
+            assert_eq!(error_msg(Pv::int(1) + Pv::int(1)), Some(exceeded.clone()));
+            assert!(!Pv::array().is_valid());
+            assert!(!Pv::null().setpath(&key, Pv::int(1)).is_valid());
+            assert_eq!(Pv::from_json("[1]").unwrap_err().message, "memory limit exceeded");
+            memory::set_memory_limit(None);
+            assert!((Pv::int(1) + Pv::int(1)).is_valid());
+            assert!(Pv::from_json(&json).is_ok());
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_memory_per_thread() {
+        // values on another thread don't count toward this thread's limit
+        let before = memory::allocated_bytes();
+        std::thread::spawn(|| {
+            let _big = PvArray::new(&vec![Pv::null(); 100_000]);
+            std::thread::spawn(|| {
+                memory::set_memory_limit(Some(10_000));
+                assert!(Pv::array().is_valid());
+            }).join().unwrap();
+        }).join().unwrap();
+        assert_eq!(memory::allocated_bytes(), before);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_memory_other_thread() {
+        // bytes come off the count they went on, whichever thread frees them
+        std::thread::spawn(|| {
+            let array = PvArray::new(&vec![Pv::null(); 100_000]);
+            let object = Pv::from_json(r#"{"a":1,"b":2}"#).unwrap();
+            assert!(memory::allocated_bytes() > 0);
+            std::thread::spawn(move || {
+                drop(array);
+                drop(object);
+                assert_eq!(memory::allocated_bytes(), 0);
+                // the count here stayed at 0, so new allocations still show up
+                let kept = PvArray::new(&[Pv::null()]);
+                assert!(memory::allocated_bytes() > 0);
+                drop(kept);
+                assert_eq!(memory::allocated_bytes(), 0);
+            }).join().unwrap();
+            assert_eq!(memory::allocated_bytes(), 0);
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_neg_truthy() {
+        assert_eq!(-Pv::int(3), Pv::int(-3));
+        assert_eq!(-Pv::float(1.5), Pv::float(-1.5));
+        assert_eq!((-Pv::int(isize::MIN)).to_json(), (PvInt::new(0) - &PvInt::new(isize::MIN)).to_string());
+        assert_eq!(error_msg(-Pv::from("a")), Some("string (\"a\") cannot be negated".into()));
+        assert!(!Pv::null().is_truthy());
+        assert!(!Pv::bool(false).is_truthy());
+        assert!(Pv::int(0).is_truthy());
+        assert!(Pv::from("").is_truthy());
+    }
+}
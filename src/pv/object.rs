@@ -1,28 +1,229 @@
 use crate::pv::private::PvFixedSize;
 
 use crate::pv::Pv;
+use crate::pv::memory::{force_alloc, Owner};
+
+// a map that remembers the order keys were first inserted in
+// `index` maps each key to where its entry is in `entries`
+pub(crate) struct PvMap {
+    entries: Vec<(Pv, Pv)>,
+    index: std::collections::HashMap<Pv, usize>,
+    // whose memory count the entries are on
+    owner: Owner,
+}
+
+// about how many bytes each entry takes up in `entries` and `index`, for the memory count
+const ENTRY_BYTES: usize = std::mem::size_of::<(Pv, Pv)>() + std::mem::size_of::<(Pv, usize)>();
+
+impl PvMap {
+    fn new() -> Self {
+        PvMap {entries: Vec::new(), index: std::collections::HashMap::new(), owner: Owner::current()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Pv) -> Option<&Pv> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    // an existing key keeps its position and just gets the new value
+    pub fn insert(&mut self, key: Pv, value: Pv) -> Option<Pv> {
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.owner.adopt(self.len() * ENTRY_BYTES);
+                force_alloc(ENTRY_BYTES);
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            },
+        }
+    }
+
+    // keeps the order of everything else
+    pub fn remove(&mut self, key: &Pv) -> Option<Pv> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        self.owner.free(ENTRY_BYTES);
+        for (key, _) in &self.entries[i..] {
+            *self.index.get_mut(key).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Pv, &Pv)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl Clone for PvMap {
+    fn clone(&self) -> Self {
+        force_alloc(self.len() * ENTRY_BYTES);
+        PvMap {entries: self.entries.clone(), index: self.index.clone(), owner: Owner::current()}
+    }
+}
+
+impl Drop for PvMap {
+    fn drop(&mut self) {
+        self.owner.free(self.len() * ENTRY_BYTES);
+    }
+}
+
+// order doesn't matter for equality
+impl PartialEq for PvMap {
+    fn eq(&self, other: &PvMap) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Eq for PvMap {}
+
+impl std::fmt::Debug for PvMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub struct PvObject {
-    data: PvFixedSize<std::collections::HashMap<Pv, Pv>>,
+    data: PvFixedSize<PvMap>,
 }
 
 impl PvObject {
     pub fn new_empty() -> Self {
-        PvObject::new(std::collections::HashMap::new())
+        PvObject {data: PvMap::new().into()}
+    }
+
+    // keys stay in the order given
+    // if a key is repeated the last value wins, but it keeps its first position
+    pub fn new(pairs: &[(Pv, Pv)]) -> Self {
+        let mut map = PvMap::new();
+        for (key, value) in pairs {
+            map.insert(key.clone(), value.clone());
+        }
+        PvObject {data: map.into()}
     }
 
-    pub fn new(data: std::collections::HashMap<Pv, Pv>) -> Self {
-        PvObject {data: data.into()}
+    pub(crate) fn get_map(&self) -> &PvMap {
+        self.data.get_data()
+    }
+
+    pub fn len(&self) -> usize {
+        self.get_map().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_map().is_empty()
+    }
+
+    pub fn get(&self, key: &Pv) -> Option<Pv> {
+        self.get_map().get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &Pv) -> bool {
+        self.get_map().get(key).is_some()
+    }
+
+    // all of these are in insertion order
+    pub fn keys(&self) -> impl Iterator<Item = &Pv> {
+        self.get_map().iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Pv> {
+        self.get_map().iter().map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Pv, &Pv)> {
+        self.get_map().iter()
+    }
+
+    // the mutating methods all change the object in place, like PvArray's do
+    // make_mut() makes sure the map is only changed in place if nothing else shares it
+
+    // an existing key keeps its position and just gets the new value
+    pub fn insert(&mut self, key: Pv, value: Pv) {
+        self.data.make_mut().insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: &Pv) -> Option<Pv> {
+        if !self.contains_key(key) {
+            // don't copy if nothing would change
+            return None;
+        }
+        self.data.make_mut().remove(key)
+    }
+
+    // keys from `other` replace keys in `self`
+    pub fn merge(&mut self, other: &PvObject) {
+        let map = self.data.make_mut();
+        for (key, value) in other.iter() {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+
+    // like merge(), but where both sides have an object for a key, those get merged too
+    pub fn merge_recursive(&mut self, other: &PvObject) {
+        let map = self.data.make_mut();
+        for (key, value) in other.iter() {
+            let merged = match (map.get(key), value) {
+                (Some(Pv::Object(old)), Pv::Object(new)) => {
+                    let mut old = old.clone();
+                    old.merge_recursive(new);
+                    old.into()
+                },
+                _ => value.clone(),
+            };
+            map.insert(key.clone(), merged);
+        }
+    }
+}
+
+// like jq, first compare the sorted keys as arrays
+// and if those are the same compare the values in sorted key order
+impl PartialOrd for PvObject {
+    fn partial_cmp(&self, other: &PvObject) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvObject {
+    fn cmp(&self, other: &PvObject) -> std::cmp::Ordering {
+        let mut keys1: Vec<&Pv> = self.keys().collect();
+        let mut keys2: Vec<&Pv> = other.keys().collect();
+        keys1.sort();
+        keys2.sort();
+        keys1.cmp(&keys2).then_with(|| {
+            let map1 = self.get_map();
+            let map2 = other.get_map();
+            keys1.iter().map(|key| map1.get(key)).cmp(keys1.iter().map(|key| map2.get(key)))
+        })
     }
 }
 
-// death implementation for now to satisfy rust
+// each entry is hashed on its own and the hashes are summed
+// so that key order doesn't matter, just like for equality
 impl std::hash::Hash for PvObject {
-    #[allow(unused_variables)] // remove this line when this gets implemented
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        // you know what
-        // i'm going to deal with this "later"
-        panic!("Tried to hash unhashable type PvObject.");
+        let map = self.get_map();
+        let sum = map.iter().fold(0u64, |sum, entry| {
+            // DefaultHasher::new() always uses the same keys
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            entry.hash(&mut hasher);
+            sum.wrapping_add(std::hash::Hasher::finish(&hasher))
+        });
+        map.len().hash(state);
+        sum.hash(state);
     }
-}
\ No newline at end of file
+}
+
+impl From<&[(Pv, Pv)]> for PvObject {
+    fn from(value: &[(Pv, Pv)]) -> Self {
+        PvObject::new(value)
+    }
+}
@@ -0,0 +1,332 @@
+use crate::pv::{Pv, PvInt, PvString, PvArray, PvObject};
+use crate::pv::memory;
+
+// deeper than this is almost certainly not real data
+// and recursing further risks overflowing the native stack
+const MAX_DEPTH: usize = 1000;
+
+// integer literals with more digits than this become floats instead
+// turning the digits into limbs takes time quadratic in how many there are
+// (and any this long are far past what a float can hold, so they're infinite anyway)
+const MAX_INT_DIGITS: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+    // object keys seen so far, so repeated ones share one string
+    // (only for this parse, so nothing outlives the values it made)
+    keys: std::collections::HashSet<PvString>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {text, pos: 0, depth: 0, keys: std::collections::HashSet::new()}
+    }
+
+    // make an error pointing at byte offset `pos`
+    // lines and columns both start at 1, and columns count codepoints
+    fn error_at(&self, pos: usize, message: &str) -> ParseError {
+        let before = &self.text[..pos];
+        let line = before.matches('\n').count() + 1;
+        let linestart = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[linestart..].chars().count() + 1;
+        ParseError {line, column, message: message.to_string()}
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        self.error_at(self.pos, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), ParseError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // the error for whatever character is at the current position
+    fn unexpected(&self) -> ParseError {
+        match self.text[self.pos..].chars().next() {
+            Some(c) => self.error(&format!("unexpected character {:?}", c)),
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Pv, ParseError> {
+        self.skip_whitespace();
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos != self.text.len() {
+            return Err(self.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Pv, ParseError> {
+        // stop as soon as anything's been refused memory, instead of parsing the rest for nothing
+        if memory::would_exceed(0) || memory::refused() {
+            return Err(self.error("memory limit exceeded"));
+        }
+        match self.peek() {
+            Some(b'n') => self.parse_literal("null", Pv::null()),
+            Some(b't') => self.parse_literal("true", Pv::bool(true)),
+            Some(b'f') => self.parse_literal("false", Pv::bool(false)),
+            Some(b'"') => Ok(self.parse_string()?.into()),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_object(),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Pv) -> Result<Pv, ParseError> {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("exceeds depth limit for parsing"));
+        }
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<Pv, ParseError> {
+        self.enter()?;
+        self.expect(b'[')?;
+        let mut array = PvArray::new_empty();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                array.append(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b']') => {
+                        self.pos += 1;
+                        break;
+                    },
+                    _ => return Err(self.error("expected ',' or ']'")),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(array.into())
+    }
+
+    fn parse_object(&mut self) -> Result<Pv, ParseError> {
+        self.enter()?;
+        self.expect(b'{')?;
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') {
+                    return Err(self.error("object keys must be strings"));
+                }
+                let key = self.parse_string_text()?;
+                let key = match self.keys.get(key.as_str()) {
+                    Some(key) => key.clone(),
+                    None => {
+                        let key = PvString::new(&key);
+                        self.keys.insert(key.clone());
+                        key
+                    },
+                };
+                self.skip_whitespace();
+                if self.peek() != Some(b':') {
+                    return Err(self.error("expected ':'"));
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+                let value = self.parse_value()?;
+                // later duplicate keys win, like jq
+                pairs.push((key.into(), value));
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        break;
+                    },
+                    _ => return Err(self.error("expected ',' or '}'")),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(PvObject::new(&pairs).into())
+    }
+
+    fn parse_string(&mut self) -> Result<PvString, ParseError> {
+        Ok(PvString::new(&self.parse_string_text()?))
+    }
+
+    fn parse_string_text(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            // copy over the longest run that needs no special handling
+            let rest = &self.text[self.pos..];
+            let run = rest.find(|c: char| c == '"' || c == '\\' || c < ' ').unwrap_or(rest.len());
+            out.push_str(&rest[..run]);
+            self.pos += run;
+            match self.peek() {
+                None => return Err(self.error_at(start, "unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    out.push(self.parse_escape()?);
+                },
+                Some(_) => return Err(self.error("unescaped control character in string")),
+            }
+        }
+    }
+
+    // the backslash has already been consumed
+    fn parse_escape(&mut self) -> Result<char, ParseError> {
+        let escapestart = self.pos - 1;
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let high = self.parse_hex4()?;
+                return Ok(if (0xd800..0xdc00).contains(&high) {
+                    // a high surrogate is only valid if a low surrogate follows
+                    let rest = &self.text[self.pos..];
+                    if rest.starts_with("\\u") {
+                        let save = self.pos;
+                        self.pos += 2;
+                        let low = self.parse_hex4()?;
+                        if (0xdc00..0xe000).contains(&low) {
+                            char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).unwrap()
+                        } else {
+                            // leave the second escape to be parsed on its own
+                            self.pos = save;
+                            char::REPLACEMENT_CHARACTER
+                        }
+                    } else {
+                        char::REPLACEMENT_CHARACTER
+                    }
+                } else {
+                    // lone low surrogates become U+FFFD too
+                    char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER)
+                });
+            },
+            _ => return Err(self.error_at(escapestart, "invalid escape")),
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = self.text.get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    // -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn parse_number(&mut self) -> Result<Pv, ParseError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        let mut integral = true;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+            integral = false;
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+            integral = false;
+        }
+        let literal = &self.text[start..self.pos];
+        if integral && literal.trim_start_matches('-').len() <= MAX_INT_DIGITS {
+            // integers are kept exactly, unless they're absurdly long
+            return Ok(PvInt::parse(literal).unwrap().into());
+        }
+        // too big numbers become infinite, which prints as the largest float
+        Ok(Pv::float(literal.parse::<f64>().unwrap()))
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+    }
+}
+
+impl Pv {
+    // parse exactly one json value (rfc 8259), with optional surrounding whitespace
+    pub fn from_json(text: &str) -> Result<Pv, ParseError> {
+        let mut parser = Parser::new(text);
+        memory::within_limit(|| parser.parse_document()).unwrap_or_else(|| Err(parser.error("memory limit exceeded")))
+    }
+}
@@ -0,0 +1,228 @@
+use crate::pv::{memory, memory_error, memory_guard, Pv, PvArray};
+
+// a path is an array of object keys (strings) and array indices (ints)
+// negative indices count from the end, and null acts like an empty object or array
+
+// an array index, if `key` is one
+// integral floats are fine too
+fn path_index(key: &Pv) -> Option<isize> {
+    match key {
+        Pv::Int(i) => i.to_isize(),
+        Pv::Float(f) => f.to_int()?.to_isize(),
+        _ => None,
+    }
+}
+
+// an invalid saying that `v` can't be indexed with `key`
+fn index_error(v: &Pv, key: &Pv) -> Pv {
+    let msg = format!("{} ({}) cannot be indexed with {} ({})", v.kind_name(), v.error_text(), key.kind_name(), key.error_text());
+    Pv::invalid_with_msg(msg.as_str().into())
+}
+
+impl Pv {
+    // the value at `path`, or null if something along the way is missing
+    pub fn getpath(&self, path: &PvArray) -> Pv {
+        let mut current = self.clone();
+        for key in path {
+            current = match (&current, key) {
+                (Pv::Null(_), Pv::String(_)) => return Pv::null(),
+                (Pv::Object(object), Pv::String(_)) => object.get(key).into(),
+                (Pv::Null(_), _) if path_index(key).is_some() => return Pv::null(),
+                (Pv::Array(array), _) if path_index(key).is_some() => array.get(path_index(key).unwrap()).into(),
+                _ => return index_error(&current, key),
+            };
+        }
+        current
+    }
+
+    // a copy with the value at `path` replaced, making objects and arrays as needed
+    // only the objects and arrays along the path get copied (and only if they're shared)
+    pub fn setpath(self, path: &PvArray, value: Pv) -> Pv {
+        memory_guard(|| self.setpath_from(path.as_slice(), value))
+    }
+
+    fn setpath_from(self, path: &[Pv], value: Pv) -> Pv {
+        let Some((key, rest)) = path.split_first() else {
+            return value;
+        };
+        match (self, key) {
+            // from Pv::object() or Pv::array() below, if the memory limit has been exceeded
+            (Pv::Invalid(v), _) => Pv::Invalid(v),
+            (Pv::Null(_), Pv::String(_)) => Pv::object().setpath_from(path, value),
+            (Pv::Object(mut object), Pv::String(_)) => {
+                let child: Pv = object.get(key).into();
+                // take the child out first so it isn't shared with `object` while it's changed
+                object.insert(key.clone(), Pv::null());
+                let child = child.setpath_from(rest, value);
+                if !child.is_valid() {
+                    return child;
+                }
+                object.insert(key.clone(), child);
+                object.into()
+            },
+            (Pv::Null(_), _) if path_index(key).is_some() => Pv::array().setpath_from(path, value),
+            (Pv::Array(mut array), _) if path_index(key).is_some() => {
+                let i = path_index(key).unwrap();
+                let child: Pv = array.get(i).into();
+                if !array.set(i, Pv::null()) {
+                    if memory::refused() {
+                        return memory_error();
+                    }
+                    // the same errors jq gives
+                    let msg = if i < 0 {"Out of bounds negative array index"} else {"Array index too large"};
+                    return Pv::invalid_with_msg(msg.into());
+                }
+                let child = child.setpath_from(rest, value);
+                if !child.is_valid() {
+                    return child;
+                }
+                array.set(i, child);
+                array.into()
+            },
+            (v, _) => index_error(&v, key),
+        }
+    }
+
+    // a copy with the values at every one of `paths` removed
+    // paths that don't exist are skipped
+    // like jq, every index is into the value from before anything was deleted
+    pub fn delpaths(self, paths: &[PvArray]) -> Pv {
+        let mut paths = paths.to_vec();
+        paths.sort();
+        // deleting the whole value covers every other path
+        if paths.first().is_some_and(|path| path.is_empty()) {
+            return Pv::null();
+        }
+        let paths: Vec<&[Pv]> = paths.iter().map(|path| path.as_slice()).collect();
+        memory_guard(|| self.delpaths_sorted(&paths, 0))
+    }
+
+    // `paths` are sorted and all longer than `start`,
+    // so the ones with the same key at `start` are next to each other
+    // everything deleted directly from this value goes in one dels() at the end
+    fn delpaths_sorted(mut self, paths: &[&[Pv]], start: usize) -> Pv {
+        let mut delkeys = Vec::new();
+        for group in paths.chunk_by(|a, b| a[start] == b[start]) {
+            let key = &group[0][start];
+            // shorter paths sort first, so if any path in the group ends here, this one does
+            // and the longer ones are inside what it deletes anyway
+            if group[0].len() == start + 1 {
+                delkeys.push(key.clone());
+                continue;
+            }
+            // take the child out first so it isn't shared while it's changed
+            let child = match (&mut self, key) {
+                (Pv::Null(_), Pv::String(_)) => continue,
+                (Pv::Null(_), _) if path_index(key).is_some() => continue,
+                (Pv::Object(object), Pv::String(_)) => match object.get(key) {
+                    Some(child) => {
+                        object.insert(key.clone(), Pv::null());
+                        child
+                    },
+                    None => continue,
+                },
+                (Pv::Array(array), _) if path_index(key).is_some() => {
+                    let i = path_index(key).unwrap();
+                    match array.get(i) {
+                        Some(child) => {
+                            array.set(i, Pv::null());
+                            child
+                        },
+                        None => continue,
+                    }
+                },
+                (v, _) => return index_error(v, key),
+            };
+            let child = child.delpaths_sorted(group, start + 1);
+            if !child.is_valid() {
+                return child;
+            }
+            match &mut self {
+                Pv::Object(object) => {
+                    object.insert(key.clone(), child);
+                },
+                Pv::Array(array) => {
+                    array.set(path_index(key).unwrap(), child);
+                },
+                _ => unreachable!(),
+            }
+        }
+        self.dels(&delkeys)
+    }
+
+    // a copy without the values at any of `keys`
+    // array indices are all counted from before anything was deleted,
+    // so repeated ones (or -1 and the last index) only delete once
+    fn dels(self, keys: &[Pv]) -> Pv {
+        match self {
+            Pv::Null(v) => Pv::Null(v),
+            Pv::Object(mut object) => {
+                if let Some(key) = keys.iter().find(|key| !matches!(key, Pv::String(_))) {
+                    return index_error(&object.into(), key);
+                }
+                for key in keys {
+                    object.remove(key);
+                }
+                object.into()
+            },
+            Pv::Array(array) => {
+                if keys.is_empty() {
+                    return array.into();
+                }
+                let len = array.len() as isize;
+                let mut indices = Vec::with_capacity(keys.len());
+                for key in keys {
+                    let Some(i) = path_index(key) else {
+                        return index_error(&array.into(), key);
+                    };
+                    indices.push(if i < 0 {i + len} else {i});
+                }
+                indices.sort_unstable();
+                let mut indices = indices.into_iter().peekable();
+                let mut kept = Vec::with_capacity(array.len());
+                for (i, v) in array.iter().enumerate() {
+                    let i = i as isize;
+                    while indices.next_if(|&del| del < i).is_some() {}
+                    if indices.peek() != Some(&i) {
+                        kept.push(v.clone());
+                    }
+                }
+                PvArray::new(&kept).into()
+            },
+            v => match keys.first() {
+                Some(key) => index_error(&v, key),
+                None => v,
+            },
+        }
+    }
+
+    // the path to every value inside this one (but not this one itself)
+    // each path comes before the paths inside it, in array and object order
+    pub fn paths(&self) -> PvArray {
+        let mut out = PvArray::new_empty();
+        self.paths_into(&PvArray::new_empty(), &mut out);
+        out
+    }
+
+    fn paths_into(&self, prefix: &PvArray, out: &mut PvArray) {
+        let mut visit = |key: Pv, child: &Pv| {
+            let mut path = prefix.clone();
+            path.append(key);
+            out.append(path.clone().into());
+            child.paths_into(&path, out);
+        };
+        match self {
+            Pv::Array(array) => {
+                for (i, child) in array.iter().enumerate() {
+                    visit(Pv::int(i as isize), child);
+                }
+            },
+            Pv::Object(object) => {
+                for (key, child) in object.iter() {
+                    visit(key.clone(), child);
+                }
+            },
+            _ => {},
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::io::Write;
+
+use crate::pv::{Pv, PvArray};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonIndent {
+    Compact,
+    Spaces(usize),
+    Tab,
+}
+
+// the parameters of an ansi color escape, like the "1;31" in "\x1b[1;31m"
+// kept inline (like jq, at most 12 characters) so JsonOptions can stay Copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonColor {
+    text: [u8; 12],
+    len: u8,
+}
+
+impl JsonColor {
+    // None unless `text` is just digits and semicolons, and short enough
+    pub fn new(text: &str) -> Option<Self> {
+        if text.len() > 12 || !text.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+            return None;
+        }
+        let mut out = JsonColor {text: [0; 12], len: text.len() as u8};
+        out.text[..text.len()].copy_from_slice(text.as_bytes());
+        Some(out)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ascii ever goes in
+        std::str::from_utf8(&self.text[..self.len as usize]).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonColors {
+    pub null: JsonColor,
+    pub bool_false: JsonColor,
+    pub bool_true: JsonColor,
+    pub number: JsonColor,
+    pub string: JsonColor,
+    pub array: JsonColor,
+    pub object: JsonColor,
+    pub key: JsonColor,
+}
+
+// the same as jq's defaults
+const DEFAULT_COLORS: &str = "0;90:0;39:0;39:0;39:0;32:1;39:1;39:34;1";
+
+impl JsonColors {
+    // parse a JQ_COLORS style list of colors separated by colons
+    // in the order null, false, true, numbers, strings, arrays, objects, object keys
+    // colors that are left off keep their default
+    // and like jq, any extra ones are ignored
+    // None if any color is malformed
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut colors = JsonColors::default();
+        let fields = [
+            &mut colors.null,
+            &mut colors.bool_false,
+            &mut colors.bool_true,
+            &mut colors.number,
+            &mut colors.string,
+            &mut colors.array,
+            &mut colors.object,
+            &mut colors.key,
+        ];
+        for (field, color) in fields.into_iter().zip(spec.split(':')) {
+            *field = JsonColor::new(color)?;
+        }
+        Some(colors)
+    }
+
+    // from $JQ_COLORS, or the defaults if that isn't set (or isn't valid)
+    pub fn from_env() -> Self {
+        std::env::var("JQ_COLORS").ok()
+            .and_then(|spec| JsonColors::parse(&spec))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for JsonColors {
+    fn default() -> Self {
+        let color = |i: usize| JsonColor::new(DEFAULT_COLORS.split(':').nth(i).unwrap()).unwrap();
+        JsonColors {
+            null: color(0),
+            bool_false: color(1),
+            bool_true: color(2),
+            number: color(3),
+            string: color(4),
+            array: color(5),
+            object: color(6),
+            key: color(7),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonOptions {
+    pub indent: JsonIndent,
+    // escape everything outside of ascii as \uXXXX
+    pub ascii: bool,
+    // write object keys sorted instead of in insertion order
+    pub sort_keys: bool,
+    // None for no colors
+    pub colors: Option<JsonColors>,
+}
+
+impl JsonOptions {
+    pub fn compact() -> Self {
+        JsonOptions {indent: JsonIndent::Compact, ascii: false, sort_keys: false, colors: None}
+    }
+
+    // what jq prints by default
+    pub fn pretty() -> Self {
+        JsonOptions {indent: JsonIndent::Spaces(2), ascii: false, sort_keys: false, colors: None}
+    }
+
+    // turn on colors from $JQ_COLORS, but only if `out` is a terminal
+    // and $NO_COLOR isn't set (https://no-color.org)
+    pub fn colored_for<T: std::io::IsTerminal>(self, out: &T) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let colors = (out.is_terminal() && !no_color).then(JsonColors::from_env);
+        JsonOptions {colors, ..self}
+    }
+
+    fn is_compact(&self) -> bool {
+        matches!(self.indent, JsonIndent::Compact | JsonIndent::Spaces(0))
+    }
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions::compact()
+    }
+}
+
+// json has no nan or infinity, so do what jq does with them
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        return "null".to_string();
+    }
+    let value = value.clamp(f64::MIN, f64::MAX);
+    let magnitude = value.abs();
+    if value.fract() == 0.0 && magnitude < 1e17 {
+        format!("{:.0}", value)
+    } else if (1e-5..1e17).contains(&magnitude) {
+        format!("{}", value)
+    } else {
+        // rust writes 1e300 where jq writes 1e+300
+        let text = format!("{:e}", value);
+        match text.split_once('e') {
+            Some((mantissa, exponent)) if !exponent.starts_with('-') => format!("{}e+{}", mantissa, exponent),
+            _ => text,
+        }
+    }
+}
+
+// json keys have to be strings
+// any other key is written as the string of its json text
+fn key_text(key: &Pv) -> String {
+    match key {
+        Pv::String(key) => key.get_str().to_string(),
+        _ => key.to_json(),
+    }
+}
+
+struct JsonWriter<'a, W: Write> {
+    out: &'a mut W,
+    opts: &'a JsonOptions,
+}
+
+impl<W: Write> JsonWriter<'_, W> {
+    // start a new line indented `level` times
+    // does nothing in compact mode
+    fn newline(&mut self, level: usize) -> std::io::Result<()> {
+        match self.opts.indent {
+            JsonIndent::Compact | JsonIndent::Spaces(0) => Ok(()),
+            JsonIndent::Spaces(n) => write!(self.out, "\n{:1$}", "", n * level),
+            JsonIndent::Tab => write!(self.out, "\n{}", "\t".repeat(level)),
+        }
+    }
+
+    // start writing in a color, if colors are on
+    fn color(&mut self, pick: fn(&JsonColors) -> JsonColor) -> std::io::Result<()> {
+        match &self.opts.colors {
+            Some(colors) => write!(self.out, "\x1b[{}m", pick(colors).as_str()),
+            None => Ok(()),
+        }
+    }
+
+    // go back to no color
+    fn reset(&mut self) -> std::io::Result<()> {
+        match &self.opts.colors {
+            Some(_) => write!(self.out, "\x1b[0m"),
+            None => Ok(()),
+        }
+    }
+
+    // every value is written in its own color
+    // punctuation is written in the color of the array or object it's in
+    fn write_value(&mut self, value: &Pv, level: usize) -> std::io::Result<()> {
+        let pick: fn(&JsonColors) -> JsonColor = match value {
+            Pv::Invalid(_) | Pv::Bytes(_) => return self.write_uncolored(value, level),
+            Pv::Null(_) => |colors| colors.null,
+            Pv::Bool(v) if v.value() => |colors| colors.bool_true,
+            Pv::Bool(_) => |colors| colors.bool_false,
+            Pv::Int(_) | Pv::Float(_) => |colors| colors.number,
+            Pv::String(_) => |colors| colors.string,
+            Pv::Array(_) => |colors| colors.array,
+            Pv::Object(_) => |colors| colors.object,
+        };
+        self.color(pick)?;
+        self.write_uncolored(value, level)?;
+        self.reset()
+    }
+
+    // write `value` without changing the color first
+    // (but anything inside it gets its own colors)
+    fn write_uncolored(&mut self, value: &Pv, level: usize) -> std::io::Result<()> {
+        match value {
+            Pv::Invalid(v) => match v.msg() {
+                Some(msg) => {
+                    write!(self.out, "<invalid:")?;
+                    self.write_value(&msg, level)?;
+                    write!(self.out, ">")
+                },
+                None => write!(self.out, "<invalid>"),
+            },
+            Pv::Null(_) => write!(self.out, "null"),
+            Pv::Bool(v) => write!(self.out, "{}", v.value()),
+            Pv::Int(v) => write!(self.out, "{}", v),
+            Pv::Float(v) => write!(self.out, "{}", format_float(v.value())),
+            Pv::String(v) => self.write_string(v.get_str()),
+            // there's no json for binary, so write it like an array of bytes
+            Pv::Bytes(v) => {
+                let bytes: Vec<Pv> = v.as_slice().iter().map(|&b| Pv::int(b as isize)).collect();
+                self.write_value(&PvArray::new(&bytes).into(), level)
+            },
+            Pv::Array(v) => {
+                let items = v.as_slice();
+                if items.is_empty() {
+                    return write!(self.out, "[]");
+                }
+                write!(self.out, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ",")?;
+                    }
+                    self.newline(level + 1)?;
+                    self.write_value(item, level + 1)?;
+                    self.color(|colors| colors.array)?;
+                }
+                self.newline(level)?;
+                write!(self.out, "]")
+            },
+            Pv::Object(v) => {
+                let map = v.get_map();
+                if map.is_empty() {
+                    return write!(self.out, "{{}}");
+                }
+                let mut entries: Vec<(&Pv, &Pv)> = map.iter().collect();
+                if self.opts.sort_keys {
+                    entries.sort_by_cached_key(|(key, _)| key_text(key));
+                }
+                write!(self.out, "{{")?;
+                for (i, (key, item)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ",")?;
+                    }
+                    self.newline(level + 1)?;
+                    self.color(|colors| colors.key)?;
+                    self.write_key(key)?;
+                    self.reset()?;
+                    self.color(|colors| colors.object)?;
+                    write!(self.out, "{}", if self.opts.is_compact() {":"} else {": "})?;
+                    self.write_value(item, level + 1)?;
+                    self.color(|colors| colors.object)?;
+                }
+                self.newline(level)?;
+                write!(self.out, "}}")
+            },
+        }
+    }
+
+    fn write_key(&mut self, key: &Pv) -> std::io::Result<()> {
+        self.write_string(&key_text(key))
+    }
+
+    fn write_string(&mut self, s: &str) -> std::io::Result<()> {
+        write!(self.out, "\"")?;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            let escape = match c {
+                '"' => Some("\\\""),
+                '\\' => Some("\\\\"),
+                '\u{8}' => Some("\\b"),
+                '\u{c}' => Some("\\f"),
+                '\n' => Some("\\n"),
+                '\r' => Some("\\r"),
+                '\t' => Some("\\t"),
+                _ => None,
+            };
+            let needs_u = c < ' ' || c == '\u{7f}' || (self.opts.ascii && !c.is_ascii());
+            if escape.is_none() && !needs_u {
+                continue;
+            }
+            // flush everything up to this character unchanged
+            self.out.write_all(&s.as_bytes()[start..i])?;
+            start = i + c.len_utf8();
+            if let Some(escape) = escape {
+                write!(self.out, "{}", escape)?;
+            } else {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(self.out, "\\u{:04x}", unit)?;
+                }
+            }
+        }
+        self.out.write_all(&s.as_bytes()[start..])?;
+        write!(self.out, "\"")
+    }
+}
+
+impl Pv {
+    pub fn write_json<W: Write>(&self, out: &mut W, opts: &JsonOptions) -> std::io::Result<()> {
+        JsonWriter {out, opts}.write_value(self, 0)
+    }
+
+    pub fn to_json_with(&self, opts: &JsonOptions) -> String {
+        let mut out = Vec::new();
+        // writing to a Vec can't fail
+        self.write_json(&mut out, opts).unwrap();
+        // only whole strings and ascii are ever written
+        String::from_utf8(out).unwrap()
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_json_with(&JsonOptions::compact())
+    }
+}
+
+impl std::fmt::Display for Pv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_json())
+    }
+}
@@ -1,7 +1,31 @@
+use crate::pv::memory::{charge, force_alloc, refuse, Owner};
+
+// with the "sync" feature the refcounts are atomic, so values can be shared between threads
+// otherwise they're plain usizes, which is faster
+
+#[cfg(not(feature = "sync"))]
+type RefCount = usize;
+
+#[cfg(feature = "sync")]
+type RefCount = std::sync::atomic::AtomicUsize;
+
+// the refcount for a new allocation
+#[cfg(not(feature = "sync"))]
+fn new_refcount() -> RefCount {
+    1
+}
+
+#[cfg(feature = "sync")]
+fn new_refcount() -> RefCount {
+    RefCount::new(1)
+}
+
+#[cfg(not(feature = "sync"))]
 macro_rules! incref {
     ($pv:expr) => (unsafe {(*$pv.data).refcount += 1})
 }
 
+#[cfg(not(feature = "sync"))]
 macro_rules! decref {
     ($pv:expr) => (unsafe {
         (*$pv.data).refcount -= 1;
@@ -9,11 +33,41 @@ macro_rules! decref {
     } == 0)
 }
 
-pub(crate) use incref;
-pub(crate) use decref;
+#[cfg(not(feature = "sync"))]
+macro_rules! refcount {
+    ($pv:expr) => (unsafe {(*$pv.data).refcount})
+}
+
+// the orderings are the same ones std::sync::Arc uses
+#[cfg(feature = "sync")]
+macro_rules! incref {
+    ($pv:expr) => (unsafe {
+        (*$pv.data).refcount.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    })
+}
+
+// the acquire fence makes every other thread's use of the data happen before it gets freed
+#[cfg(feature = "sync")]
+macro_rules! decref {
+    ($pv:expr) => (unsafe {
+        if (*$pv.data).refcount.fetch_sub(1, std::sync::atomic::Ordering::Release) == 1 {
+            std::sync::atomic::fence(std::sync::atomic::Ordering::Acquire);
+            true
+        } else {
+            false
+        }
+    })
+}
+
+// acquire so that changing the data after seeing a refcount of 1 is safe
+#[cfg(feature = "sync")]
+macro_rules! refcount {
+    ($pv:expr) => (unsafe {(*$pv.data).refcount.load(std::sync::atomic::Ordering::Acquire)})
+}
 
 struct PvFixedSizeData<T> {
-    refcount: usize,
+    refcount: RefCount,
+    owner: Owner,
     data: T,
 }
 
@@ -31,22 +85,25 @@ impl<T> PvFixedSize<T> {
         let layout = PvFixedSize::<T>::get_layout();
 
         let data = unsafe {std::alloc::alloc(layout)} as *mut PvFixedSizeData<T>;
+        if data.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        force_alloc(layout.size());
 
-        unsafe {std::ptr::write(data, PvFixedSizeData::<T> {refcount: 1, data: val});}
+        unsafe {std::ptr::write(data, PvFixedSizeData::<T> {refcount: new_refcount(), owner: Owner::current(), data: val});}
 
         PvFixedSize::<T> {data}
     }
 
-    // get a mutable slice reference to the array data
+    // get a mutable reference to the data
     // only use when refcount = 1
-    pub fn get_data_mut(&self) -> &mut T {
+    pub(crate) fn get_data_mut(&mut self) -> &mut T {
         unsafe {&mut (*self.data).data}
     }
 
-    // get an immutable reference to the array data
-    // only use when refcount = 1
+    // get an immutable reference to the data
     pub fn get_data(&self) -> &T {
-        self.get_data_mut()
+        unsafe {&(*self.data).data}
     }
 }
 
@@ -54,19 +111,28 @@ impl<T: Clone> PvFixedSize<T> {
     // move one copy of this value out
     // will reuse the old allocation if possible
     pub fn move_out(self) -> Self {
-        if unsafe {(*self.data).refcount} == 1 {
+        if refcount!(self) == 1 {
             self
         } else {
             PvFixedSize::<T>::new(self.get_data().clone())
         }
     }
+
+    // get a mutable reference to the data
+    // copies it first if it's shared, the same way move_out() does
+    pub fn make_mut(&mut self) -> &mut T {
+        if refcount!(self) != 1 {
+            *self = PvFixedSize::<T>::new(self.get_data().clone());
+        }
+        self.get_data_mut()
+    }
 }
 
 impl<T: Copy> PvFixedSize<T> {
     // move one copy of this value out
     // will reuse the old allocation if possible
     pub fn copy_out(self) -> Self {
-        if unsafe {(*self.data).refcount} == 1 {
+        if refcount!(self) == 1 {
             self
         } else {
             PvFixedSize::<T>::new(*self.get_data())
@@ -84,17 +150,26 @@ impl<T: std::fmt::Debug> std::fmt::Debug for PvFixedSize<T> {
 
 impl<T> Drop for PvFixedSize<T> {
     fn drop(&mut self) {
-        if (decref!(self)) {
+        if decref!(self) {
             let layout = PvFixedSize::<T>::get_layout();
 
             // this should take the value out so it gets dropped
-            unsafe {std::ptr::read(self.data);}
+            let data = unsafe {std::ptr::read(self.data)};
 
             unsafe {std::alloc::dealloc(self.data as *mut u8, layout);}
+            data.owner.free(layout.size());
         }
     }
 }
 
+// the data is only changed when the refcount is 1
+// so sharing it is as safe as sharing an Arc<T>
+#[cfg(feature = "sync")]
+unsafe impl<T: Send + Sync> Send for PvFixedSize<T> {}
+
+#[cfg(feature = "sync")]
+unsafe impl<T: Send + Sync> Sync for PvFixedSize<T> {}
+
 impl<T> Clone for PvFixedSize<T> {
     fn clone(&self) -> Self {
         incref!(self);
@@ -122,16 +197,21 @@ impl<T> From<T> for PvFixedSize<T> {
     }
 }
 
-#[derive(Copy, Clone)]
 struct PvArrayData {
-    refcount: usize,
-    len: usize,
+    refcount: RefCount,
+    len: usize, // how many elements of the allocation are initialized
     alloc_size: usize,
+    owner: Owner,
 }
 
+// a view of `len` elements starting `offset` elements into the allocation
+// slices share the allocation with the array they came from
+// and only get their own copy once something changes them
 #[derive(Eq)]
 pub struct PvpArray<T> {
     data: *mut PvArrayData,
+    offset: usize,
+    len: usize,
     _data: std::marker::PhantomData<[T]>,
 }
 
@@ -147,39 +227,56 @@ macro_rules! clone_to_uninit {
 
 impl<T> PvpArray<T> {
     // get a Layout that fits a PvArrayData with `size` T's after it
-    fn get_layout(size: usize) -> std::alloc::Layout {
-        let mut layout = std::alloc::Layout::new::<PvArrayData>();
-        // i'm not assuming nightly rust with std::alloc::Layout::repeat()
-        for _ in 0..size {
-            // no errors need to be handled
-            // the total size does not overflow isize (i'm assuming this one)
-            (layout, _) = layout.extend(
-                std::alloc::Layout::new::<T>()
-            ).unwrap();
-        }
-        layout
+    // None if that's too big to allocate at all
+    fn get_layout(size: usize) -> Option<std::alloc::Layout> {
+        let (layout, _) = std::alloc::Layout::new::<PvArrayData>().extend(
+            std::alloc::Layout::array::<T>(size).ok()?
+        ).ok()?;
+        Some(layout)
     }
 
-    // allocates enough space for `len` array elements
-    pub fn new_empty_sized(size: usize) -> Self {
-        let layout = PvpArray::<T>::get_layout(size);
-
+    // only use with a layout from get_layout(size) that's already been counted
+    fn alloc_sized(size: usize, layout: std::alloc::Layout) -> Self {
         let data = unsafe {std::alloc::alloc(layout)} as *mut PvArrayData;
+        if data.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+
+        // written without dropping what was there before, since that's uninitialized
+        unsafe {std::ptr::write(data, PvArrayData {refcount: new_refcount(), len: 0, alloc_size: size, owner: Owner::current()});}
+
+        PvpArray::<T> {data, offset: 0, len: 0, _data: std::marker::PhantomData}
+    }
 
-        unsafe {*data = PvArrayData {refcount: 1, len: 0, alloc_size: size};}
+    // allocates enough space for `size` array elements
+    // if `force` is false, None if that would go over this thread's memory limit
+    // if it's true, it's allocated anyway (and memory::within_limit() sees the refusal)
+    fn new_empty_sized_or(size: usize, force: bool) -> Option<Self> {
+        let layout = PvpArray::<T>::get_layout(size);
+        if !force && layout.is_none() {
+            refuse();
+            return None;
+        }
+        let layout = layout.expect("capacity overflow");
+        charge(layout.size(), force).then(|| PvpArray::<T>::alloc_sized(size, layout))
+    }
 
-        PvpArray::<T> {data, _data: std::marker::PhantomData}
+    // allocates enough space for `size` array elements
+    // `size` is only a guess at what's needed
+    // so if it would go over this thread's memory limit, there's no space for any
+    // (and memory::within_limit() sees the refusal)
+    pub fn new_empty_sized(size: usize) -> Self {
+        PvpArray::<T>::new_empty_sized_or(size, false)
+            .or_else(|| PvpArray::<T>::new_empty_sized_or(0, true))
+            .unwrap()
     }
 
     pub fn new_empty() -> Self {
         PvpArray::<T>::new_empty_sized(16) // any size would work
     }
 
-    // get a mutable slice reference to the array data
-    // only use when refcount = 1
-    fn get_data_mut(&self) -> &mut [std::mem::MaybeUninit<T>] {
-        let data = unsafe {*self.data};
-
+    // pointer to the first element of the whole allocation
+    fn get_elements(&self) -> *mut std::mem::MaybeUninit<T> {
         let layout = std::alloc::Layout::new::<PvArrayData>();
         // no errors need to be handled
         // the total size does not overflow isize (i'm assuming this one)
@@ -189,30 +286,70 @@ impl<T> PvpArray<T> {
         // i could probably just get the size of layout
         // but just to be safe :)
 
+        unsafe {(self.data as *mut u8).add(offset) as *mut std::mem::MaybeUninit<T>}
+    }
+
+    // get a mutable slice reference to the elements in this view
+    // only use when is_unique()
+    #[allow(clippy::mut_from_ref)]
+    fn get_data_mut(&self) -> &mut [std::mem::MaybeUninit<T>] {
         unsafe {
-            std::slice::from_raw_parts_mut(
-                (self.data as *mut u8).add(offset) as *mut std::mem::MaybeUninit<T>,
-                data.len
-            )
+            std::slice::from_raw_parts_mut(self.get_elements().add(self.offset), self.len)
         }
     }
 
-    // get an immutable reference to the array data
-    // only use when refcount = 1
-    fn get_data(&self) -> &[T] {
+    // get an immutable reference to the elements in this view
+    pub(crate) fn get_data(&self) -> &[T] {
         unsafe {std::mem::transmute::<_, _>(self.get_data_mut())}
     }
 
     pub fn len(&self) -> usize {
-        unsafe {*self.data}.len
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn alloc_size(&self) -> usize {
+        unsafe {(*self.data).alloc_size}
+    }
+
+    // whether this can be changed in place
+    // (nothing else shares the allocation, and this isn't a slice of it)
+    fn is_unique(&self) -> bool {
+        refcount!(self) == 1 && self.offset == 0 && self.len == unsafe {(*self.data).len}
+    }
+
+    // only use when is_unique()
+    unsafe fn set_len(&mut self, len: usize) {
+        (*self.data).len = len;
+        self.len = len;
+    }
+
+    // whether both are the same view of the same allocation
+    pub(crate) fn ptr_eq(&self, other: &PvpArray<T>) -> bool {
+        self.data == other.data && self.offset == other.offset && self.len == other.len
+    }
+
+    // a view of elements `start` up to (not including) `end`
+    // shares this array's allocation, so it's O(1)
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        assert!(start <= end && end <= self.len);
+        incref!(self);
+        PvpArray {data: self.data, offset: self.offset + start, len: end - start, _data: std::marker::PhantomData}
     }
 }
 
 impl<T: Clone> PvpArray<T> {
     pub fn new(pvs: &[T]) -> Self {
-        let out = PvpArray::<T>::new_empty_sized(pvs.len() * 2); // any >= str.len()
+        // any >= str.len()
+        // but only exactly that much if there isn't room for more
+        let mut out = PvpArray::<T>::new_empty_sized_or(pvs.len() * 2, false)
+            .or_else(|| PvpArray::<T>::new_empty_sized_or(pvs.len(), true))
+            .unwrap();
         unsafe {
-            (*out.data).len = pvs.len();
+            out.set_len(pvs.len());
         }
 
         clone_to_uninit!(pvs, out.get_data_mut(), 0, pvs.len());
@@ -222,100 +359,206 @@ impl<T: Clone> PvpArray<T> {
 
     // move one copy of this array out and resize its allocation
     // will reuse the old allocation if possible
-    unsafe fn resize_move(&mut self, newsize: usize) {
-        let data = *self.data;
-
-        if data.refcount == 1 {
-            assert!(newsize >= data.len); // just a suggestion
+    // if `force` is false, false (leaving the array as it was) if that would go over this thread's memory limit
+    // if it's true, it's allocated anyway (and memory::within_limit() sees the refusal)
+    unsafe fn resize_move_or(&mut self, newsize: usize, force: bool) -> bool {
+        if self.is_unique() {
+            assert!(newsize >= (*self.data).len); // just a suggestion
 
-            let oldlayout = PvpArray::<T>::get_layout(data.alloc_size);
+            let oldlayout = PvpArray::<T>::get_layout((*self.data).alloc_size).unwrap();
 
             let newlayout = PvpArray::<T>::get_layout(newsize);
+            if !force && newlayout.is_none() {
+                refuse();
+                return false;
+            }
+            let newlayout = newlayout.expect("capacity overflow");
+            (*self.data).owner.adopt(oldlayout.size());
+            if newlayout.size() > oldlayout.size() {
+                if !charge(newlayout.size() - oldlayout.size(), force) {
+                    return false;
+                }
+            } else {
+                (*self.data).owner.free(oldlayout.size() - newlayout.size());
+            }
             
-            self.data = std::alloc::realloc(self.data as *mut u8, oldlayout, newlayout.size()) as *mut PvArrayData;
+            let data = std::alloc::realloc(self.data as *mut u8, oldlayout, newlayout.size()) as *mut PvArrayData;
+            if data.is_null() {
+                std::alloc::handle_alloc_error(newlayout);
+            }
+            self.data = data;
 
             (*self.data).alloc_size = newsize;
         } else {
-            let out = PvpArray::<T>::new_empty_sized(newsize);
-            (*out.data).len = data.len;
-            clone_to_uninit!(self.get_data(), out.get_data_mut(), 0, data.len);
+            let Some(mut out) = PvpArray::<T>::new_empty_sized_or(newsize, force) else {
+                return false;
+            };
+            out.set_len(self.len);
+            clone_to_uninit!(self.get_data(), out.get_data_mut(), 0, self.len);
             *self = out
         }
+        true
+    }
+
+    // make this array unique with room for `needed` elements
+    // twice that if it fits in this thread's memory limit, and just `needed` if not
+    // everything but resize() grows by elements that already exist somewhere
+    // so that much is always allocated, and memory::within_limit() sees it if it went over
+    // (a refusal never leaves a value with elements missing)
+    fn grow(&mut self, needed: usize) {
+        if self.is_unique() && self.alloc_size() >= needed {
+            return;
+        }
+        unsafe {
+            if !self.resize_move_or(needed.saturating_mul(2), false) {
+                self.resize_move_or(needed, true);
+            }
+        }
+    }
+
+    // copy the elements into their own allocation if anything else can see them
+    fn make_unique(&mut self) {
+        self.grow(self.len);
     }
 
     pub fn append(&mut self, other: T) {
-        let data = unsafe {*self.data};
+        let len = self.len;
 
-        if data.refcount > 1 || data.alloc_size < data.len + 1 {unsafe {
-            self.resize_move((data.len + 1) * 2);
-        }};
+        self.grow(len + 1);
 
         unsafe {
-            (*self.data).len += 1;
+            self.set_len(len + 1);
         }
 
-        self.get_data_mut()[data.len].write(other);
+        self.get_data_mut()[len].write(other);
     }
 
     pub fn concat(&mut self, other: &PvpArray<T>) {
-        let data = unsafe {*self.data};
-        let otherdata = unsafe {*other.data};
+        self.extend_from_slice(other.get_data());
+    }
+
+    pub fn extend_from_slice(&mut self, other: &[T]) {
+        let len = self.len;
 
-        if data.refcount > 1 && data.alloc_size < data.len + otherdata.len {unsafe {
-            self.resize_move((data.len + otherdata.len) * 2);
-        }};
+        self.grow(len + other.len());
 
         unsafe {
-            (*self.data).len += otherdata.len;
+            self.set_len(len + other.len());
         }
 
-        clone_to_uninit!(other.get_data(), self.get_data_mut(), data.len, otherdata.len);
+        clone_to_uninit!(other, self.get_data_mut(), len, other.len());
     }
 
-    pub fn pop(&mut self) {
-        let data = unsafe {*self.data};
-
-        if data.refcount > 1 {unsafe {
-            self.resize_move(data.alloc_size);
-        }};
+    // like Vec::resize(), only for growing
+    // `newlen` is just a number, so this is the one that can be refused:
+    // false (leaving the array as it was) if it would go over this thread's memory limit
+    pub fn resize(&mut self, newlen: usize, value: T) -> bool {
+        let len = self.len;
+        assert!(newlen >= len);
 
+        if (!self.is_unique() || self.alloc_size() < newlen) && !unsafe {self.resize_move_or(newlen, false)} {
+            return false;
+        }
 
         unsafe {
-            // drop the last element
-            self.get_data_mut()[data.len - 1].assume_init_drop();
-            (*self.data).len -= 1;
+            self.set_len(newlen);
+        }
+
+        for element in &mut self.get_data_mut()[len..] {
+            element.write(value.clone());
         }
+        true
+    }
+
+    pub fn pop(&mut self) {
+        self.popn(1);
     }
 
     pub fn popn(&mut self, n: usize) {
-        let data = unsafe {*self.data};
+        let len = self.len;
 
-        if data.refcount > 1 {unsafe {
-            self.resize_move(data.alloc_size);
-        }};
+        if !self.is_unique() {
+            // the elements are still someone else's, so the view can just get shorter
+            self.len = len - n;
+            return;
+        }
 
         unsafe {
             // drop the last n elements
-            for i in data.len - n..data.len {
+            for i in len - n..len {
                 self.get_data_mut()[i].assume_init_drop();
             }
 
-            (*self.data).len -= n;
+            self.set_len(len - n);
         }
     }
 
     pub fn get(&self, i: usize) -> T {
         self.get_data()[i].clone()
     }
+
+    pub fn set(&mut self, i: usize, value: T) {
+        self.make_mut()[i] = value;
+    }
+
+    // shifts everything from `i` on up by one
+    pub fn insert(&mut self, i: usize, value: T) {
+        let len = self.len;
+        assert!(i <= len);
+
+        self.grow(len + 1);
+
+        unsafe {
+            self.set_len(len + 1);
+            let elements = self.get_data_mut().as_mut_ptr();
+            std::ptr::copy(elements.add(i), elements.add(i + 1), len - i);
+        }
+
+        self.get_data_mut()[i].write(value);
+    }
+
+    // shifts everything after `i` down by one
+    pub fn remove(&mut self, i: usize) -> T {
+        let len = self.len;
+        assert!(i < len);
+
+        self.make_unique();
+
+        unsafe {
+            let elements = self.get_data_mut().as_mut_ptr();
+            let value = (*elements.add(i)).assume_init_read();
+            std::ptr::copy(elements.add(i + 1), elements.add(i), len - i - 1);
+            self.set_len(len - 1);
+            value
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.popn(self.len() - len);
+        }
+    }
+
+    pub fn reverse(&mut self) {
+        self.make_mut().reverse();
+    }
+
+    // get a mutable slice of the elements
+    // copies them first if the array is shared
+    pub fn make_mut(&mut self) -> &mut [T] {
+        self.make_unique();
+
+        unsafe {std::mem::transmute::<_, _>(self.get_data_mut())}
+    }
 }
 
 impl<T: std::fmt::Debug> PvpArray<T> {
     // for Debug implementation
     pub fn fmt(&self, f: &mut std::fmt::Formatter<'_>, typename: &str) -> std::fmt::Result {
-        let data = unsafe {*self.data};
         f.debug_struct(typename)
-         .field("len", &data.len)
-         .field("alloc_size", &data.alloc_size)
+         .field("offset", &self.offset)
+         .field("len", &self.len)
+         .field("alloc_size", &self.alloc_size())
          .field("data", &self.get_data())
          .finish()
     }
@@ -323,11 +566,12 @@ impl<T: std::fmt::Debug> PvpArray<T> {
 
 impl<T> Drop for PvpArray<T> {
     fn drop(&mut self) {
-        if (decref!(self)) {
-            let size = unsafe {(*self.data).alloc_size};
-            let layout = PvpArray::<T>::get_layout(size);
+        if decref!(self) {
+            let layout = PvpArray::<T>::get_layout(self.alloc_size()).unwrap();
 
-            for mval in self.get_data_mut() {
+            // every initialized element, not just the ones in this view
+            let elements = unsafe {std::slice::from_raw_parts_mut(self.get_elements(), (*self.data).len)};
+            for mval in elements {
                 // take the value (run its destructor)
                 // std::mem::MaybeUninit::assume_init_read() instead of
                 // std::mem::MaybeUninit::assume_init() so it doesn't
@@ -335,15 +579,24 @@ impl<T> Drop for PvpArray<T> {
                 unsafe {mval.assume_init_read();}
             }
 
+            let owner = unsafe {std::ptr::read(&(*self.data).owner)};
             unsafe {std::alloc::dealloc(self.data as *mut u8, layout);}
+            owner.free(layout.size());
         }
     }
 }
 
+// same as for PvFixedSize
+#[cfg(feature = "sync")]
+unsafe impl<T: Send + Sync> Send for PvpArray<T> {}
+
+#[cfg(feature = "sync")]
+unsafe impl<T: Send + Sync> Sync for PvpArray<T> {}
+
 impl<T> Clone for PvpArray<T> {
     fn clone(&self) -> Self {
         incref!(self);
-        PvpArray {data: self.data, _data: std::marker::PhantomData}
+        PvpArray {data: self.data, offset: self.offset, len: self.len, _data: std::marker::PhantomData}
     }
 }
 
@@ -363,4 +616,4 @@ impl<T: Clone> From<&[T]> for PvpArray<T> {
     fn from(value: &[T]) -> Self {
         PvpArray::new(value)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,383 @@
+// the same PvFixedSize and PvpArray as private.rs, but without any unsafe code
+// it's slower, but useful for checking that the fast ones are right
+// (turned on with the "safe" feature)
+
+use crate::pv::memory::{charge, force_alloc, refuse, track_alloc, Owner};
+
+#[cfg(not(feature = "sync"))]
+type Shared<T> = std::rc::Rc<T>;
+
+#[cfg(feature = "sync")]
+type Shared<T> = std::sync::Arc<T>;
+
+// a value and how many bytes the memory count has for it
+// this goes inside the Rc so the bytes get taken off exactly once, when the value is dropped
+// the bytes have to be counted already
+struct Tracked<T> {
+    value: T,
+    bytes: usize,
+    owner: Owner,
+}
+
+impl<T> Tracked<Vec<T>> {
+    // how many bytes `capacity` elements take, counted with memory::charge()
+    // None if they were refused
+    fn count(capacity: usize, force: bool) -> Option<usize> {
+        let bytes = capacity.checked_mul(std::mem::size_of::<T>()).filter(|&bytes| bytes <= isize::MAX as usize);
+        if !force && bytes.is_none() {
+            refuse();
+            return None;
+        }
+        let bytes = bytes.expect("capacity overflow");
+        charge(bytes, force).then_some(bytes)
+    }
+
+    // an empty Vec with room for `capacity` elements
+    // None if that was refused (see memory::charge())
+    fn with_capacity_or(capacity: usize, force: bool) -> Option<Self> {
+        let bytes = Tracked::<Vec<T>>::count(capacity, force)?;
+        Some(Tracked {value: Vec::with_capacity(capacity), bytes, owner: Owner::current()})
+    }
+
+    // an empty Vec with room for `wanted` elements
+    // or just `needed` if there isn't enough memory left for that many
+    fn with_capacity_forced(wanted: usize, needed: usize) -> Self {
+        Tracked::with_capacity_or(wanted, false)
+            .or_else(|| Tracked::with_capacity_or(needed, true))
+            .unwrap()
+    }
+
+    // make room for `extra` more elements
+    // twice what's needed if it fits in this thread's memory limit, and just that if not
+    // false (changing nothing) if it was refused (see memory::charge())
+    fn reserve_or(&mut self, extra: usize, force: bool) -> bool {
+        let needed = self.value.len().saturating_add(extra);
+        let capacity = self.value.capacity();
+        if needed <= capacity {
+            return true;
+        }
+        let wanted = needed.max(capacity.saturating_mul(2));
+        self.owner.adopt(self.bytes);
+        let (newcapacity, bytes) = match Tracked::<Vec<T>>::count(wanted - capacity, false) {
+            Some(bytes) => (wanted, bytes),
+            None if force => (needed, Tracked::<Vec<T>>::count(needed - capacity, true).unwrap()),
+            None => return false,
+        };
+        self.bytes += bytes;
+        self.value.reserve_exact(newcapacity - self.value.len());
+        self.retrack();
+        true
+    }
+
+    // after the Vec might have been reallocated
+    // (it doesn't grow past what reserve_or() made room for, so this is only ever small)
+    fn retrack(&mut self) {
+        let bytes = self.value.capacity() * std::mem::size_of::<T>();
+        self.owner.adopt(self.bytes);
+        if bytes > self.bytes {
+            track_alloc(bytes - self.bytes);
+        } else {
+            self.owner.free(self.bytes - bytes);
+        }
+        self.bytes = bytes;
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.owner.free(self.bytes);
+    }
+}
+
+pub struct PvFixedSize<T> {
+    data: Shared<Tracked<T>>,
+}
+
+impl<T> PvFixedSize<T> {
+    pub fn new(val: T) -> Self {
+        let bytes = std::mem::size_of::<T>();
+        force_alloc(bytes);
+        PvFixedSize::<T> {data: Shared::new(Tracked {value: val, bytes, owner: Owner::current()})}
+    }
+
+    // get a mutable reference to the data
+    // only use when refcount = 1
+    pub(crate) fn get_data_mut(&mut self) -> &mut T {
+        &mut Shared::get_mut(&mut self.data).expect("get_data_mut() on a shared value").value
+    }
+
+    // get an immutable reference to the data
+    pub fn get_data(&self) -> &T {
+        &self.data.value
+    }
+}
+
+impl<T: Clone> PvFixedSize<T> {
+    // move one copy of this value out
+    // will reuse the old allocation if possible
+    pub fn move_out(self) -> Self {
+        if Shared::strong_count(&self.data) == 1 {
+            self
+        } else {
+            PvFixedSize::<T>::new(self.get_data().clone())
+        }
+    }
+
+    // get a mutable reference to the data
+    // copies it first if it's shared, the same way move_out() does
+    pub fn make_mut(&mut self) -> &mut T {
+        if Shared::strong_count(&self.data) != 1 {
+            *self = PvFixedSize::<T>::new(self.get_data().clone());
+        }
+        self.get_data_mut()
+    }
+}
+
+impl<T: Copy> PvFixedSize<T> {
+    // move one copy of this value out
+    // will reuse the old allocation if possible
+    pub fn copy_out(self) -> Self {
+        if Shared::strong_count(&self.data) == 1 {
+            self
+        } else {
+            PvFixedSize::<T>::new(*self.get_data())
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for PvFixedSize<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct(std::any::type_name::<PvFixedSize<T>>())
+         .field("data", &self.get_data())
+         .finish()
+    }
+}
+
+impl<T> Clone for PvFixedSize<T> {
+    fn clone(&self) -> Self {
+        PvFixedSize::<T> {data: self.data.clone()}
+    }
+}
+
+impl<T: PartialEq> PartialEq for PvFixedSize<T> {
+    fn eq(&self, other: &PvFixedSize<T>) -> bool {
+        self.get_data() == other.get_data()
+    }
+}
+
+impl<T: Eq> Eq for PvFixedSize<T> {}
+
+impl<T: std::hash::Hash> std::hash::Hash for PvFixedSize<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get_data().hash(state);
+    }
+}
+
+impl<T> From<T> for PvFixedSize<T> {
+    fn from(value: T) -> Self {
+        PvFixedSize::<T>::new(value)
+    }
+}
+
+// a view of `len` elements starting at `offset` in the shared Vec
+// slices share the Vec with the array they came from
+// and only get their own copy once something changes them
+pub struct PvpArray<T> {
+    data: Shared<Tracked<Vec<T>>>,
+    offset: usize,
+    len: usize,
+}
+
+impl<T> PvpArray<T> {
+    // allocates enough space for `size` array elements
+    // `size` is only a guess at what's needed
+    // so if it would go over this thread's memory limit, there's no space for any
+    // (and memory::within_limit() sees the refusal)
+    pub fn new_empty_sized(size: usize) -> Self {
+        let data = Tracked::with_capacity_or(size, false).unwrap_or_else(|| Tracked {value: Vec::new(), bytes: 0, owner: Owner::current()});
+        PvpArray::<T> {data: Shared::new(data), offset: 0, len: 0}
+    }
+
+    pub fn new_empty() -> Self {
+        PvpArray::<T>::new_empty_sized(16) // any size would work
+    }
+
+    // get an immutable reference to the elements in this view
+    pub(crate) fn get_data(&self) -> &[T] {
+        &self.data.value[self.offset..self.offset + self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn alloc_size(&self) -> usize {
+        self.data.value.capacity()
+    }
+
+    // whether both are the same view of the same allocation
+    pub(crate) fn ptr_eq(&self, other: &PvpArray<T>) -> bool {
+        Shared::ptr_eq(&self.data, &other.data) && self.offset == other.offset && self.len == other.len
+    }
+
+    // a view of elements `start` up to (not including) `end`
+    // shares this array's allocation, so it's O(1)
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        assert!(start <= end && end <= self.len);
+        PvpArray {data: self.data.clone(), offset: self.offset + start, len: end - start}
+    }
+}
+
+impl<T: Clone> PvpArray<T> {
+    pub fn new(pvs: &[T]) -> Self {
+        let mut data = Tracked::with_capacity_forced(pvs.len() * 2, pvs.len());
+        data.value.extend_from_slice(pvs);
+        PvpArray::<T> {data: Shared::new(data), offset: 0, len: pvs.len()}
+    }
+
+    // change the Vec with `f`, copying the elements first if anything else can see them
+    // beforehand the view is made the whole Vec, with room for `extra` more elements
+    // if `force` is false, None (leaving the array as it was) if that would go over this thread's memory limit
+    // if it's true, it's allocated anyway (and memory::within_limit() sees the refusal)
+    fn change_or<R>(&mut self, extra: usize, force: bool, f: impl FnOnce(&mut Vec<T>) -> R) -> Option<R> {
+        if Shared::strong_count(&self.data) != 1 {
+            let needed = self.len.saturating_add(extra);
+            let mut data = match Tracked::with_capacity_or(needed.saturating_mul(2), false) {
+                Some(data) => data,
+                None => Tracked::with_capacity_or(needed, force)?,
+            };
+            data.value.extend_from_slice(self.get_data());
+            self.data = Shared::new(data);
+            self.offset = 0;
+        }
+        let data = Shared::get_mut(&mut self.data).unwrap();
+        // nothing else can see the elements outside the view, so they can go
+        data.value.truncate(self.offset + self.len);
+        data.value.drain(..self.offset);
+        self.offset = 0;
+        if !data.reserve_or(extra, force) {
+            return None;
+        }
+        let out = f(&mut data.value);
+        data.retrack();
+        self.len = data.value.len();
+        Some(out)
+    }
+
+    // everything but resize() grows by elements that already exist somewhere
+    // so that much is always allocated, and memory::within_limit() sees it if it went over
+    // (a refusal never leaves a value with elements missing)
+    fn change<R>(&mut self, extra: usize, f: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        self.change_or(extra, true, f).unwrap()
+    }
+
+    pub fn append(&mut self, other: T) {
+        self.change(1, |data| data.push(other));
+    }
+
+    pub fn concat(&mut self, other: &PvpArray<T>) {
+        self.extend_from_slice(other.get_data());
+    }
+
+    pub fn extend_from_slice(&mut self, other: &[T]) {
+        self.change(other.len(), |data| data.extend_from_slice(other));
+    }
+
+    // like Vec::resize(), only for growing
+    // `newlen` is just a number, so this is the one that can be refused:
+    // false (leaving the array as it was) if it would go over this thread's memory limit
+    pub fn resize(&mut self, newlen: usize, value: T) -> bool {
+        self.change_or(newlen - self.len, false, |data| data.resize(newlen, value)).is_some()
+    }
+
+    pub fn pop(&mut self) {
+        self.popn(1);
+    }
+
+    pub fn popn(&mut self, n: usize) {
+        let len = self.len - n;
+        if Shared::strong_count(&self.data) != 1 {
+            // the elements are still someone else's, so the view can just get shorter
+            self.len = len;
+            return;
+        }
+        self.change(0, |data| data.truncate(len));
+    }
+
+    pub fn get(&self, i: usize) -> T {
+        self.get_data()[i].clone()
+    }
+
+    pub fn set(&mut self, i: usize, value: T) {
+        self.make_mut()[i] = value;
+    }
+
+    // shifts everything from `i` on up by one
+    pub fn insert(&mut self, i: usize, value: T) {
+        self.change(1, |data| data.insert(i, value));
+    }
+
+    // shifts everything after `i` down by one
+    pub fn remove(&mut self, i: usize) -> T {
+        self.change(0, |data| data.remove(i))
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.popn(self.len() - len);
+        }
+    }
+
+    pub fn reverse(&mut self) {
+        self.make_mut().reverse();
+    }
+
+    // get a mutable slice of the elements
+    // copies them first if the array is shared
+    pub fn make_mut(&mut self) -> &mut [T] {
+        self.change(0, |_| {});
+        &mut Shared::get_mut(&mut self.data).unwrap().value
+    }
+}
+
+impl<T: std::fmt::Debug> PvpArray<T> {
+    // for Debug implementation
+    pub fn fmt(&self, f: &mut std::fmt::Formatter<'_>, typename: &str) -> std::fmt::Result {
+        f.debug_struct(typename)
+         .field("offset", &self.offset)
+         .field("len", &self.len)
+         .field("alloc_size", &self.alloc_size())
+         .field("data", &self.get_data())
+         .finish()
+    }
+}
+
+impl<T> Clone for PvpArray<T> {
+    fn clone(&self) -> Self {
+        PvpArray {data: self.data.clone(), offset: self.offset, len: self.len}
+    }
+}
+
+impl<T: PartialEq> PartialEq for PvpArray<T> {
+    fn eq(&self, other: &PvpArray<T>) -> bool {
+        self.get_data() == other.get_data()
+    }
+}
+
+impl<T: Eq> Eq for PvpArray<T> {}
+
+impl<T: std::hash::Hash> std::hash::Hash for PvpArray<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get_data().hash(state);
+    }
+}
+
+impl<T: Clone> From<&[T]> for PvpArray<T> {
+    fn from(value: &[T]) -> Self {
+        PvpArray::new(value)
+    }
+}
@@ -0,0 +1,687 @@
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, EnumAccess, Error as _, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::ser::{Error as _, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer};
+
+use crate::pv::{memory, Pv, PvInvalid, PvNull, PvBool, PvInt, PvFloat, PvString, PvBytes, PvArray, PvObject};
+
+// Pv goes to and from the serde data model like this:
+//   null <-> unit (and none)
+//   bool <-> bool
+//   int <-> i64, or i128 or u128 if it doesn't fit (and then f64 if none of those fit)
+//   float <-> f64
+//   string <-> str
+//   bytes <-> bytes
+//   array <-> seq
+//   object <-> map (keys can be any value, but most formats only allow strings)
+// invalid values can't be serialized
+//
+// Pv is also a Deserializer and PvSerializer is a Serializer, using the same mapping
+// so from_pv() and to_pv() go between a Pv and any serde type without another format in between
+// enums are like serde_json's: unit variants are strings, and the rest are {"variant": value}
+
+impl Serialize for Pv {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Pv::Invalid(v) => v.serialize(serializer),
+            Pv::Null(v) => v.serialize(serializer),
+            Pv::Bool(v) => v.serialize(serializer),
+            Pv::Int(v) => v.serialize(serializer),
+            Pv::Float(v) => v.serialize(serializer),
+            Pv::String(v) => v.serialize(serializer),
+            Pv::Bytes(v) => v.serialize(serializer),
+            Pv::Array(v) => v.serialize(serializer),
+            Pv::Object(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for PvInvalid {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(S::Error::custom("invalid values cannot be serialized"))
+    }
+}
+
+impl Serialize for PvNull {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl Serialize for PvBool {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(self.value())
+    }
+}
+
+impl Serialize for PvInt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(value) = self.to_isize() {
+            return serializer.serialize_i64(value as i64);
+        }
+        let text = self.to_string();
+        if let Ok(value) = text.parse::<i128>() {
+            serializer.serialize_i128(value)
+        } else if let Ok(value) = text.parse::<u128>() {
+            serializer.serialize_u128(value)
+        } else {
+            serializer.serialize_f64(self.to_f64())
+        }
+    }
+}
+
+impl Serialize for PvFloat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.value())
+    }
+}
+
+impl Serialize for PvString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl Serialize for PvBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_slice())
+    }
+}
+
+impl Serialize for PvArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for value in self {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for PvObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+fn int_from_i128(value: i128) -> Pv {
+    match isize::try_from(value) {
+        Ok(value) => Pv::int(value),
+        // too big for an isize, so let PvInt make a big int
+        Err(_) => PvInt::parse(&value.to_string()).unwrap().into(),
+    }
+}
+
+fn int_from_u128(value: u128) -> Pv {
+    match isize::try_from(value) {
+        Ok(value) => Pv::int(value),
+        Err(_) => PvInt::parse(&value.to_string()).unwrap().into(),
+    }
+}
+
+struct PvVisitor;
+
+impl<'de> Visitor<'de> for PvVisitor {
+    type Value = Pv;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Pv, E> {
+        Ok(Pv::null())
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Pv, E> {
+        Ok(Pv::null())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Pv, D::Error> {
+        Pv::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<Pv, E> {
+        Ok(Pv::bool(value))
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Pv, E> {
+        self.visit_i128(value as i128)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Pv, E> {
+        self.visit_i128(value as i128)
+    }
+
+    fn visit_i128<E: serde::de::Error>(self, value: i128) -> Result<Pv, E> {
+        Ok(int_from_i128(value))
+    }
+
+    fn visit_u128<E: serde::de::Error>(self, value: u128) -> Result<Pv, E> {
+        Ok(int_from_u128(value))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Pv, E> {
+        Ok(Pv::float(value))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Pv, E> {
+        Ok(value.into())
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, value: &[u8]) -> Result<Pv, E> {
+        Ok(Pv::bytes(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Pv, A::Error> {
+        let mut array = PvArray::new_empty_sized(cautious::<Pv>(seq.size_hint().unwrap_or(16)));
+        while let Some(value) = seq.next_element()? {
+            array.append(value);
+        }
+        Ok(array.into())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Pv, A::Error> {
+        let mut pairs = Vec::with_capacity(cautious::<(Pv, Pv)>(map.size_hint().unwrap_or(0)));
+        while let Some(pair) = map.next_entry()? {
+            pairs.push(pair);
+        }
+        Ok(PvObject::new(&pairs).into())
+    }
+}
+
+// how many `T`s to make room for up front, when the input says there will be `hint` of them
+// the input could say anything, so it's capped at 1MiB worth (like serde's own deserializers do)
+fn cautious<T>(hint: usize) -> usize {
+    const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+    hint.min(MAX_PREALLOC_BYTES / std::mem::size_of::<T>().max(1))
+}
+
+impl<'de> Deserialize<'de> for Pv {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pv, D::Error> {
+        deserializer.deserialize_any(PvVisitor)
+    }
+}
+
+// the others go through Pv and then check that the right kind came out
+
+macro_rules! pv_deserialize_impl {
+    ($type:ident $variant:ident $kind:literal) => {
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$type, D::Error> {
+                match Pv::deserialize(deserializer)? {
+                    Pv::$variant(value) => Ok(value),
+                    other => Err(D::Error::custom(format!("expected {}, got {}", $kind, other.kind_name()))),
+                }
+            }
+        }
+    };
+}
+
+pv_deserialize_impl!(PvNull Null "null");
+pv_deserialize_impl!(PvBool Bool "boolean");
+pv_deserialize_impl!(PvInt Int "int");
+pv_deserialize_impl!(PvString String "string");
+pv_deserialize_impl!(PvArray Array "array");
+pv_deserialize_impl!(PvObject Object "object");
+
+// ints are fine where a float is wanted
+impl<'de> Deserialize<'de> for PvFloat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PvFloat, D::Error> {
+        match Pv::deserialize(deserializer)? {
+            Pv::Float(value) => Ok(value),
+            Pv::Int(value) => Ok(PvFloat::new(value.to_f64())),
+            other => Err(D::Error::custom(format!("expected float, got {}", other.kind_name()))),
+        }
+    }
+}
+
+// formats without a bytes type (like json) write them as an array of ints
+// so those are accepted too
+impl<'de> Deserialize<'de> for PvBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PvBytes, D::Error> {
+        match Pv::deserialize(deserializer)? {
+            Pv::Bytes(value) => Ok(value),
+            Pv::Array(array) => {
+                let mut out = PvBytes::new_empty_sized(array.len());
+                for value in &array {
+                    let byte = match value {
+                        Pv::Int(value) => value.to_isize().and_then(|value| u8::try_from(value).ok()),
+                        _ => None,
+                    };
+                    out.append(byte.ok_or_else(|| D::Error::custom("expected an array of bytes"))?);
+                }
+                Ok(out)
+            },
+            other => Err(D::Error::custom(format!("expected bytes, got {}", other.kind_name()))),
+        }
+    }
+}
+
+// the error from from_pv() and to_pv()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerdeError {
+    pub message: String,
+}
+
+impl std::fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerdeError {message: msg.to_string()}
+    }
+}
+
+impl serde::ser::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerdeError {message: msg.to_string()}
+    }
+}
+
+// turn a Pv into any type that can be deserialized
+pub fn from_pv<T: DeserializeOwned>(value: Pv) -> Result<T, SerdeError> {
+    T::deserialize(value)
+}
+
+// turn any type that can be serialized into a Pv
+// an error if that would go over this thread's memory limit
+pub fn to_pv<T: Serialize + ?Sized>(value: &T) -> Result<Pv, SerdeError> {
+    memory::within_limit(|| value.serialize(PvSerializer)).unwrap_or_else(|| Err(serde::ser::Error::custom("memory limit exceeded")))
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Pv {
+    type Deserializer = Pv;
+
+    fn into_deserializer(self) -> Pv {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Pv {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Pv::Invalid(_) => Err(serde::de::Error::custom("invalid values cannot be deserialized")),
+            Pv::Null(_) => visitor.visit_unit(),
+            Pv::Bool(v) => visitor.visit_bool(v.value()),
+            Pv::Int(v) => match v.to_isize() {
+                Some(v) => visitor.visit_i64(v as i64),
+                None => {
+                    let text = v.to_string();
+                    if let Ok(i) = text.parse::<i128>() {
+                        visitor.visit_i128(i)
+                    } else if let Ok(u) = text.parse::<u128>() {
+                        visitor.visit_u128(u)
+                    } else {
+                        visitor.visit_f64(v.to_f64())
+                    }
+                },
+            },
+            Pv::Float(v) => visitor.visit_f64(v.value()),
+            Pv::String(v) => visitor.visit_str(v.as_str()),
+            Pv::Bytes(v) => visitor.visit_bytes(v.as_slice()),
+            Pv::Array(v) => {
+                let mut seq = SeqDeserializer::new(v.iter().cloned());
+                let out = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(out)
+            },
+            Pv::Object(v) => {
+                let mut map = MapDeserializer::new(v.iter().map(|(key, value)| (key.clone(), value.clone())));
+                let out = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(out)
+            },
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Pv::Null(_) => visitor.visit_none(),
+            v => visitor.visit_some(v),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Pv::String(v) => visitor.visit_enum(v.as_str().into_deserializer()),
+            Pv::Object(v) if v.len() == 1 => {
+                let (variant, value) = v.iter().next().unwrap();
+                visitor.visit_enum(PvEnum {variant: variant.clone(), value: value.clone()})
+            },
+            v => Err(serde::de::Error::custom(format!("expected string or object with one key for an enum, got {}", v.kind_name()))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// an enum variant with data, from {"variant": value}
+struct PvEnum {
+    variant: Pv,
+    value: Pv,
+}
+
+impl<'de> EnumAccess<'de> for PvEnum {
+    type Error = SerdeError;
+    type Variant = Pv;
+
+    fn variant_seed<V: serde::de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Pv), SerdeError> {
+        Ok((seed.deserialize(self.variant)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Pv {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: serde::de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_map(visitor)
+    }
+}
+
+// builds a Pv out of anything that can be serialized
+struct PvSerializer;
+
+// {variant: value}
+fn variant_object(variant: &str, value: Pv) -> Pv {
+    PvObject::new(&[(variant.into(), value)]).into()
+}
+
+impl Serializer for PvSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+    type SerializeSeq = PvSeqSerializer;
+    type SerializeTuple = PvSeqSerializer;
+    type SerializeTupleStruct = PvSeqSerializer;
+    type SerializeTupleVariant = PvSeqSerializer;
+    type SerializeMap = PvMapSerializer;
+    type SerializeStruct = PvMapSerializer;
+    type SerializeStructVariant = PvMapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Pv, SerdeError> {
+        Ok(Pv::bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Pv, SerdeError> {
+        Ok(int_from_i128(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Pv, SerdeError> {
+        Ok(int_from_i128(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Pv, SerdeError> {
+        Ok(int_from_i128(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Pv, SerdeError> {
+        Ok(int_from_i128(v.into()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Pv, SerdeError> {
+        Ok(int_from_i128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Pv, SerdeError> {
+        Ok(int_from_u128(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Pv, SerdeError> {
+        Ok(int_from_u128(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Pv, SerdeError> {
+        Ok(int_from_u128(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Pv, SerdeError> {
+        Ok(int_from_u128(v.into()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Pv, SerdeError> {
+        Ok(int_from_u128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Pv, SerdeError> {
+        Ok(Pv::float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Pv, SerdeError> {
+        Ok(Pv::float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Pv, SerdeError> {
+        Ok((&*v.encode_utf8(&mut [0; 4])).into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Pv, SerdeError> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Pv, SerdeError> {
+        Ok(Pv::bytes(v))
+    }
+
+    fn serialize_none(self) -> Result<Pv, SerdeError> {
+        Ok(Pv::null())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Pv, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Pv, SerdeError> {
+        Ok(Pv::null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Pv, SerdeError> {
+        Ok(Pv::null())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Pv, SerdeError> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Pv, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Pv, SerdeError> {
+        Ok(variant_object(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<PvSeqSerializer, SerdeError> {
+        Ok(PvSeqSerializer {array: PvArray::new_empty_sized(len.unwrap_or(16)), variant: None})
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<PvSeqSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<PvSeqSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<PvSeqSerializer, SerdeError> {
+        Ok(PvSeqSerializer {array: PvArray::new_empty_sized(len), variant: Some(variant)})
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<PvMapSerializer, SerdeError> {
+        Ok(PvMapSerializer {object: PvObject::new_empty(), key: None, variant: None})
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<PvMapSerializer, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<PvMapSerializer, SerdeError> {
+        Ok(PvMapSerializer {object: PvObject::new_empty(), key: None, variant: Some(variant)})
+    }
+}
+
+// for sequences and tuples
+// tuple variants end up as {variant: [...]}
+struct PvSeqSerializer {
+    array: PvArray,
+    variant: Option<&'static str>,
+}
+
+impl PvSeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.array.append(value.serialize(PvSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Pv, SerdeError> {
+        Ok(match self.variant {
+            Some(variant) => variant_object(variant, self.array.into()),
+            None => self.array.into(),
+        })
+    }
+}
+
+impl SerializeSeq for PvSeqSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for PvSeqSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for PvSeqSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for PvSeqSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+// for maps and structs
+// struct variants end up as {variant: {...}}
+struct PvMapSerializer {
+    object: PvObject,
+    // the key from serialize_key(), waiting for its value
+    key: Option<Pv>,
+    variant: Option<&'static str>,
+}
+
+impl PvMapSerializer {
+    fn finish(self) -> Result<Pv, SerdeError> {
+        Ok(match self.variant {
+            Some(variant) => variant_object(variant, self.object.into()),
+            None => self.object.into(),
+        })
+    }
+}
+
+impl SerializeMap for PvMapSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(key.serialize(PvSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().ok_or_else(|| serde::ser::Error::custom("serialize_value() called before serialize_key()"))?;
+        self.object.insert(key, value.serialize(PvSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for PvMapSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.object.insert(key.into(), value.serialize(PvSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for PvMapSerializer {
+    type Ok = Pv;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.object.insert(key.into(), value.serialize(PvSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Pv, SerdeError> {
+        self.finish()
+    }
+}
@@ -1,13 +1,45 @@
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
-pub struct PvInvalid;
+use crate::pv::private::PvFixedSize;
+use crate::pv::bigint::PvBigInt;
+use crate::pv::{Pv, op_error};
+
+// the message can be any value, like jq's jv_invalid_with_msg()
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub struct PvInvalid(Option<PvFixedSize<Pv>>);
 
 impl PvInvalid {
     pub fn new() -> Self {
-        PvInvalid
+        PvInvalid(None)
+    }
+
+    pub fn with_msg(msg: Pv) -> Self {
+        PvInvalid(Some(msg.into()))
+    }
+
+    pub fn msg(&self) -> Option<Pv> {
+        self.0.as_ref().map(|msg| msg.get_data().clone())
+    }
+}
+
+// invalids without a message come first
+impl PartialOrd for PvInvalid {
+    fn partial_cmp(&self, other: &PvInvalid) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvInvalid {
+    fn cmp(&self, other: &PvInvalid) -> std::cmp::Ordering {
+        self.msg().cmp(&other.msg())
+    }
+}
+
+impl Default for PvInvalid {
+    fn default() -> Self {
+        PvInvalid::new()
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone)]
 pub struct PvNull;
 
 impl PvNull {
@@ -16,13 +48,23 @@ impl PvNull {
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
+impl Default for PvNull {
+    fn default() -> Self {
+        PvNull::new()
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone)]
 pub struct PvBool(bool);
 
 impl PvBool {
     pub fn new(value: bool) -> Self {
         PvBool(value)
     }
+
+    pub fn value(&self) -> bool {
+        self.0
+    }
 }
 
 impl From<bool> for PvBool {
@@ -31,12 +73,85 @@ impl From<bool> for PvBool {
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
-pub struct PvInt(isize);
+// small values are stored inline
+// anything that doesn't fit in an isize is a heap allocated PvBigInt
+// (and a Big never holds a value that would fit in a Small)
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+enum PvIntRepr {
+    Small(isize),
+    Big(PvBigInt),
+}
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub struct PvInt(PvIntRepr);
 
 impl PvInt {
     pub fn new(value: isize) -> Self {
-        PvInt(value)
+        PvInt(PvIntRepr::Small(value))
+    }
+
+    fn from_big(value: PvBigInt) -> Self {
+        match value.to_isize() {
+            Some(value) => PvInt::new(value),
+            None => PvInt(PvIntRepr::Big(value)),
+        }
+    }
+
+    fn to_big(&self) -> PvBigInt {
+        match &self.0 {
+            PvIntRepr::Small(v) => PvBigInt::from_isize(*v),
+            PvIntRepr::Big(v) => v.clone(),
+        }
+    }
+
+    // None if the value doesn't fit
+    pub fn to_isize(&self) -> Option<isize> {
+        match self.0 {
+            PvIntRepr::Small(v) => Some(v),
+            PvIntRepr::Big(_) => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match &self.0 {
+            PvIntRepr::Small(v) => *v as f64,
+            PvIntRepr::Big(v) => v.to_f64(),
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match &self.0 {
+            PvIntRepr::Small(v) => *v < 0,
+            PvIntRepr::Big(v) => v.is_negative(),
+        }
+    }
+
+    // None when dividing by zero, where `/` gives an invalid
+    pub fn checked_div(&self, other: &PvInt) -> Option<PvInt> {
+        if let (PvIntRepr::Small(v1), PvIntRepr::Small(v2)) = (&self.0, &other.0) {
+            if let Some(v) = v1.checked_div(*v2) {
+                return Some(PvInt::new(v));
+            }
+        }
+        self.to_big().divrem(&other.to_big()).map(|(q, _)| PvInt::from_big(q))
+    }
+
+    // None when dividing by zero, where `%` gives an invalid
+    pub fn checked_rem(&self, other: &PvInt) -> Option<PvInt> {
+        if let (PvIntRepr::Small(v1), PvIntRepr::Small(v2)) = (&self.0, &other.0) {
+            if let Some(v) = v1.checked_rem(*v2) {
+                return Some(PvInt::new(v));
+            }
+        }
+        self.to_big().divrem(&other.to_big()).map(|(_, r)| PvInt::from_big(r))
+    }
+
+    // parse an optional minus sign and then decimal digits, of any length
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.parse::<isize>() {
+            Ok(v) => Some(PvInt::new(v)),
+            Err(_) => PvBigInt::parse(text).map(PvInt::from_big),
+        }
     }
 }
 
@@ -46,23 +161,178 @@ impl From<isize> for PvInt {
     }
 }
 
+impl PartialOrd for PvInt {
+    fn partial_cmp(&self, other: &PvInt) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvInt {
+    fn cmp(&self, other: &PvInt) -> std::cmp::Ordering {
+        match (&self.0, &other.0) {
+            (PvIntRepr::Small(v1), PvIntRepr::Small(v2)) => v1.cmp(v2),
+            _ => self.to_big().cmp(&other.to_big()),
+        }
+    }
+}
+
+impl std::fmt::Display for PvInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            PvIntRepr::Small(v) => write!(f, "{}", v),
+            PvIntRepr::Big(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+// try the inline op first, and only do it with PvBigInts if that overflows
+// so overflow always gives the exact answer, in debug and release builds alike
 macro_rules! pvint_op_impl {
-    ($optrait:ident $op:ident) => {
+    ($optrait:ident $op:ident $checked:ident $bigop:expr) => {
         impl std::ops::$optrait<&PvInt> for PvInt {
             type Output = Self;
 
             fn $op(self, other: &PvInt) -> Self {
-                PvInt(self.0.$op(other.0))
+                if let (PvIntRepr::Small(v1), PvIntRepr::Small(v2)) = (&self.0, &other.0) {
+                    if let Some(v) = v1.$checked(*v2) {
+                        return PvInt::new(v);
+                    }
+                }
+                PvInt::from_big($bigop(&self.to_big(), &other.to_big()))
+            }
+        }
+    }
+}
+
+pvint_op_impl!(Add add checked_add PvBigInt::add);
+pvint_op_impl!(Sub sub checked_sub PvBigInt::sub);
+pvint_op_impl!(Mul mul checked_mul PvBigInt::mul);
+
+// dividing by zero gives an invalid, the same as Pv's `/` and `%`
+// so these two give a Pv instead of a PvInt
+macro_rules! pvint_div_impl {
+    ($optrait:ident $op:ident $checked:ident) => {
+        impl std::ops::$optrait<&PvInt> for PvInt {
+            type Output = Pv;
+
+            fn $op(self, other: &PvInt) -> Pv {
+                match self.$checked(other) {
+                    Some(v) => v.into(),
+                    None => op_error(&self.into(), &other.clone().into(), "divided because the divisor is zero"),
+                }
+            }
+        }
+    }
+}
+
+pvint_div_impl!(Div div checked_div);
+pvint_div_impl!(Rem rem checked_rem);
+
+#[derive(Debug, Copy, Clone)]
+pub struct PvFloat(f64);
+
+impl PvFloat {
+    pub fn new(value: f64) -> Self {
+        PvFloat(value)
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+
+    // the exactly equal int, if there is one
+    pub fn to_int(&self) -> Option<PvInt> {
+        if self.0.is_finite() && self.0.fract() == 0.0 {
+            Some(PvInt::from_big(PvBigInt::from_f64(self.0)))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<f64> for PvFloat {
+    fn from(value: f64) -> Self {
+        PvFloat::new(value)
+    }
+}
+
+// unlike f64 this is a total equality
+// so nan == nan (but -0.0 == 0.0 still)
+impl PartialEq for PvFloat {
+    fn eq(&self, other: &PvFloat) -> bool {
+        self.0 == other.0 || (self.0.is_nan() && other.0.is_nan())
+    }
+}
+
+impl Eq for PvFloat {}
+
+// nan is less than every other number, like in jq
+impl PartialOrd for PvFloat {
+    fn partial_cmp(&self, other: &PvFloat) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PvFloat {
+    fn cmp(&self, other: &PvFloat) -> std::cmp::Ordering {
+        match (self.0.is_nan(), other.0.is_nan()) {
+            (true, true) => std::cmp::Ordering::Equal,
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            (false, false) => self.0.partial_cmp(&other.0).unwrap(),
+        }
+    }
+}
+
+impl PvFloat {
+    // exact, even when the int is too big to be a float
+    pub fn cmp_int(&self, other: &PvInt) -> std::cmp::Ordering {
+        if self.0.is_nan() {
+            return std::cmp::Ordering::Less;
+        }
+        if self.0.is_infinite() {
+            return if self.0 > 0.0 {std::cmp::Ordering::Greater} else {std::cmp::Ordering::Less};
+        }
+        // compare the integer part first, and then any fraction breaks the tie
+        let floor = self.0.floor();
+        match PvFloat(floor).to_int().unwrap().cmp(other) {
+            std::cmp::Ordering::Equal if self.0 > floor => std::cmp::Ordering::Greater,
+            ordering => ordering,
+        }
+    }
+}
+
+impl std::hash::Hash for PvFloat {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // equal values need equal bits
+        let value = if self.0 == 0.0 {
+            0.0
+        } else if self.0.is_nan() {
+            f64::NAN
+        } else {
+            self.0
+        };
+        value.to_bits().hash(state);
+    }
+}
+
+macro_rules! pvfloat_op_impl {
+    ($optrait:ident $op:ident) => {
+        impl std::ops::$optrait<&PvFloat> for PvFloat {
+            type Output = Self;
+
+            fn $op(self, other: &PvFloat) -> Self {
+                PvFloat(self.0.$op(other.0))
             }
         }
     }
 }
 
-pvint_op_impl!(Add add);
-pvint_op_impl!(Sub sub);
-pvint_op_impl!(Mul mul);
-pvint_op_impl!(Div div);
-pvint_op_impl!(Rem rem);
+pvfloat_op_impl!(Add add);
+pvfloat_op_impl!(Sub sub);
+pvfloat_op_impl!(Mul mul);
+pvfloat_op_impl!(Div div);
+
 
 #[cfg(test)]
 mod tests {
@@ -71,7 +341,14 @@ mod tests {
     // the most pointless tests known to mankind
     #[test]
     fn test_invalid() {
-        assert_eq!(PvInvalid::new(), PvInvalid);
+        assert_eq!(PvInvalid::new(), PvInvalid(None));
+    }
+
+    #[test]
+    fn test_invalid_msg() {
+        assert_eq!(PvInvalid::with_msg("oops".into()).msg(), Some("oops".into()));
+        assert_eq!(PvInvalid::new().msg(), None);
+        assert_ne!(PvInvalid::with_msg("oops".into()), PvInvalid::new());
     }
 
     #[test]
@@ -86,7 +363,7 @@ mod tests {
 
     #[test]
     fn test_int() {
-        assert_eq!(PvInt::new(15), PvInt(15));
+        assert_eq!(PvInt::new(15), PvInt(PvIntRepr::Small(15)));
     }
 
     // real
@@ -94,4 +371,125 @@ mod tests {
     fn test_int_add() {
         assert_eq!(PvInt::new(15) + PvInt::new(3), PvInt::new(18));
     }
+
+    #[test]
+    fn test_int_overflow() {
+        let max = PvInt::new(isize::MAX);
+        let big = max.clone() + &PvInt::new(1);
+        assert_eq!(big.to_isize(), None);
+        assert_eq!(big.clone() - &PvInt::new(1), max);
+        assert_eq!(PvInt::new(isize::MIN) / &PvInt::new(-1), Pv::from(PvInt::new(isize::MIN) * &PvInt::new(-1)));
+        assert_eq!(PvInt::new(isize::MIN) % &PvInt::new(-1), Pv::int(0));
+    }
+
+    #[test]
+    fn test_int_div_zero() {
+        let msg = |v: Pv| match v {
+            Pv::Invalid(v) => v.msg(),
+            _ => None,
+        };
+        assert_eq!(msg(PvInt::new(1) / PvInt::new(0)), Some("int (1) and int (0) cannot be divided because the divisor is zero".into()));
+        assert_eq!(msg(PvInt::new(1) % PvInt::new(0)), Some("int (1) and int (0) cannot be divided because the divisor is zero".into()));
+        let big = PvInt::parse("100000000000000000000").unwrap();
+        assert!(msg(big.clone() / &PvInt::new(0)).is_some());
+        assert!(msg(big % &PvInt::new(0)).is_some());
+    }
+
+    #[test]
+    fn test_bigint_arithmetic() {
+        let a = PvInt::parse("123456789012345678901234567890").unwrap();
+        let b = PvInt::parse("-987654321098765432109876543210").unwrap();
+        assert_eq!((a.clone() * &b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+        assert_eq!((a.clone() + &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!((b.clone() / &a).to_string(), "-8");
+        assert_eq!((b.clone() % &a).to_string(), "-9000000000900000000090");
+        assert_eq!(a.clone() * &b / &b, Pv::from(a.clone()));
+        assert_eq!(a.to_f64(), 1.2345678901234568e29);
+    }
+
+    // a number from little endian base 2^32 limbs, made with just multiplying and adding
+    fn from_limbs(limbs: &[u32]) -> PvInt {
+        let base = PvInt::new(1 << 32);
+        limbs.iter().rev().fold(PvInt::new(0), |acc, &limb| acc * &base + &PvInt::new(limb as isize))
+    }
+
+    #[test]
+    fn test_bigint_divrem() {
+        // limbs near the edges are the ones that make a quotient limb guess wrong
+        let picks = [0, 1, 2, 0x7fff_ffff, 0x8000_0000, 0xffff_fffe, 0xffff_ffff, 0x1234_5678];
+        let mut seed = 12345u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        for _ in 0..2000 {
+            let a: Vec<u32> = (0..next() % 8 + 1).map(|_| picks[next() % picks.len()]).collect();
+            let mut b: Vec<u32> = (0..next() % 5 + 1).map(|_| picks[next() % picks.len()]).collect();
+            *b.last_mut().unwrap() |= 1;
+            let (a, b) = (from_limbs(&a), from_limbs(&b));
+            let a = if next() % 2 == 0 {a} else {PvInt::new(0) - &a};
+            let q = a.checked_div(&b).unwrap();
+            let r = a.checked_rem(&b).unwrap();
+            assert_eq!(q * &b + &r, a);
+            // the remainder is smaller than the divisor, with the sign of the dividend
+            assert!(r.is_negative() == a.is_negative() || r == PvInt::new(0));
+            let r = if r.is_negative() {PvInt::new(0) - &r} else {r};
+            assert!(r < b);
+        }
+    }
+
+    #[test]
+    fn test_bigint_to_f64() {
+        // the same rounding as going through the decimal text
+        let check = |v: PvInt| assert_eq!(v.to_f64(), v.to_string().parse::<f64>().unwrap(), "{}", v);
+        let shift = from_limbs(&[0, 0, 0, 1]);
+        // exactly halfway rounds to even, and anything past halfway rounds up
+        check(from_limbs(&[1, 0x20_0000]) * &shift);
+        check(from_limbs(&[3, 0x20_0000]) * &shift);
+        check(from_limbs(&[1, 0x20_0000]) * &shift + &PvInt::new(1));
+        check(from_limbs(&[0xffff_ffff; 5]));
+        check(PvInt::new(0) - &from_limbs(&[0x8000_0000, 0, 0x8000_0001]));
+        check(PvInt::parse("123456789012345678901234567890").unwrap());
+        let huge = from_limbs(&[0xffff_ffff; 40]);
+        assert_eq!(huge.to_f64(), f64::INFINITY);
+        assert_eq!((PvInt::new(0) - &huge).to_f64(), f64::NEG_INFINITY);
+        assert_eq!(PvFloat::new(f64::MAX).to_int().unwrap().to_f64(), f64::MAX);
+    }
+
+    #[test]
+    fn test_int_ord() {
+        let big = PvInt::parse("100000000000000000000").unwrap();
+        assert!(PvInt::new(-1) < PvInt::new(1));
+        assert!(PvInt::new(isize::MAX) < big);
+        assert!(PvInt::new(isize::MIN) > PvInt::new(0) - &big);
+    }
+
+    #[test]
+    fn test_float_ord() {
+        assert!(PvFloat::new(f64::NAN) < PvFloat::new(f64::NEG_INFINITY));
+        assert!(PvFloat::new(-1.5) < PvFloat::new(1.5));
+        assert_eq!(PvFloat::new(-0.0).cmp(&PvFloat::new(0.0)), std::cmp::Ordering::Equal);
+        assert_eq!(PvFloat::new(2.5).cmp_int(&PvInt::new(2)), std::cmp::Ordering::Greater);
+        assert_eq!(PvFloat::new(-2.5).cmp_int(&PvInt::new(-2)), std::cmp::Ordering::Less);
+        assert_eq!(PvFloat::new(2.0).cmp_int(&PvInt::new(2)), std::cmp::Ordering::Equal);
+        assert_eq!(PvFloat::new(1e20).cmp_int(&PvInt::parse("100000000000000000001").unwrap()), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn test_float_eq() {
+        assert_eq!(PvFloat::new(f64::NAN), PvFloat::new(f64::NAN));
+        assert_eq!(PvFloat::new(-0.0), PvFloat::new(0.0));
+        assert_ne!(PvFloat::new(1.0), PvFloat::new(1.5));
+    }
+
+    #[test]
+    fn test_float_to_int() {
+        assert_eq!(PvFloat::new(3.0).to_int(), Some(PvInt::new(3)));
+        assert_eq!(PvFloat::new(3.5).to_int(), None);
+        // too big for an isize, but not for a bigint
+        assert_eq!(PvFloat::new(1e300).to_int().map(|v| v.to_f64()), Some(1e300));
+        assert_eq!(PvFloat::new(f64::INFINITY).to_int(), None);
+        assert_eq!(PvFloat::new(1e20).to_int(), PvInt::parse("100000000000000000000"));
+        assert_eq!(PvFloat::new(f64::NAN).to_int(), None);
+    }
 }
\ No newline at end of file
@@ -1,177 +1,372 @@
-use crate::pv::private::{incref, decref};
+use crate::pv::private::PvpArray;
 
-#[derive(Copy, Clone)]
-struct PvStringData {
-    refcount: usize,
-    len: usize,
-    alloc_size: usize,
+use crate::pv::{Pv, PvArray};
+
+// strings up to this many bytes are stored in the PvString itself
+// (this makes the inline variant the same size as the heap one)
+const INLINE_CAP: usize = 22;
+
+#[derive(Clone)]
+enum PvStringRepr {
+    Inline(u8, [u8; INLINE_CAP]),
+    Heap(PvpArray<u8>),
 }
 
-#[derive(Eq)]
+// the bytes are always valid utf-8
+// (the only ways to make one are from a &str or from a slice of another one on char boundaries)
+#[derive(Clone)]
 pub struct PvString {
-    data: *mut PvStringData,
+    data: PvStringRepr,
 }
 
-impl PvString {
-    // get a Layout that fits a PvStringData with `size` bytes after it
-    fn get_layout(size: usize) -> std::alloc::Layout {
-        let layout = std::alloc::Layout::new::<PvStringData>();
-        // no errors need to be handled
-        // the total size does not overflow isize (i'm assuming this one)
-        let (layout, _) = layout.extend(
-            // align (1) is not zero
-            // align (1) is a power of two
-            // size (size) does not overflow isize when rounded up to align (i'm assuming this one)
-            std::alloc::Layout::from_size_align(size, 1).unwrap()
-        ).unwrap();
-        layout
-    }
+thread_local! {
+    // strings from PvString::intern()
+    // these stay allocated (and counted against the memory limit) until PvString::clear_interned()
+    // nothing interns on its own, so only callers that asked for it pay for this
+    static INTERNED: std::cell::RefCell<std::collections::HashSet<PvString>> = std::cell::RefCell::new(std::collections::HashSet::new());
+}
 
+impl PvString {
     // allocates enough space for `len` bytes of string
     pub fn new_empty_sized(size: usize) -> Self {
-        let layout = PvString::get_layout(size);
+        if size <= INLINE_CAP {
+            return PvString {data: PvStringRepr::Inline(0, [0; INLINE_CAP])};
+        }
+        PvString {data: PvStringRepr::Heap(PvpArray::new_empty_sized(size))}
+    }
+
+    pub fn new_empty() -> Self {
+        PvString::new_empty_sized(0)
+    }
 
-        let data = unsafe {std::alloc::alloc(layout)} as *mut PvStringData;
+    pub fn new(str: &str) -> Self {
+        if str.len() <= INLINE_CAP {
+            let mut bytes = [0; INLINE_CAP];
+            bytes[..str.len()].copy_from_slice(str.as_bytes());
+            return PvString {data: PvStringRepr::Inline(str.len() as u8, bytes)};
+        }
+        PvString {data: PvStringRepr::Heap(PvpArray::new(str.as_bytes()))}
+    }
 
-        unsafe {*data = PvStringData {refcount: 1, len: 0, alloc_size: size};}
+    // the same string as new(), but equal long strings share one allocation
+    // meant for things like object keys that get repeated a lot
+    pub fn intern(str: &str) -> Self {
+        if str.len() <= INLINE_CAP {
+            // nothing to share
+            return PvString::new(str);
+        }
+        INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(existing) = interned.get(str) {
+                return existing.clone();
+            }
+            let s = PvString::new(str);
+            interned.insert(s.clone());
+            s
+        })
+    }
 
-        PvString {data}
+    // like intern(), but this string is the one that gets interned if there isn't one yet
+    // a slice (or a string with spare room) is copied first,
+    // so the table doesn't keep a bigger allocation alive than the string needs
+    pub fn into_interned(self) -> Self {
+        let PvStringRepr::Heap(data) = &self.data else {
+            return self;
+        };
+        let s = if data.len() < data.alloc_size() {PvString::new(self.get_str())} else {self};
+        INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(existing) = interned.get(s.get_str()) {
+                return existing.clone();
+            }
+            interned.insert(s.clone());
+            s
+        })
     }
 
-    pub fn new_empty() -> Self {
-        PvString::new_empty_sized(16) // any size would work
+    // forget every interned string
+    // strings that were already handed out stay valid
+    pub fn clear_interned() {
+        INTERNED.with(|interned| interned.borrow_mut().clear());
     }
 
-    pub fn new(str: &str) -> Self {
-        let out = PvString::new_empty_sized(str.len() * 2); // any >= str.len()
-        unsafe {
-            (*out.data).len = str.len();
+    #[cfg(test)]
+    pub(crate) fn is_inline(&self) -> bool {
+        matches!(self.data, PvStringRepr::Inline(..))
+    }
+
+    fn get_bytes(&self) -> &[u8] {
+        match &self.data {
+            PvStringRepr::Inline(len, bytes) => &bytes[..*len as usize],
+            PvStringRepr::Heap(data) => data.get_data(),
+        }
+    }
+
+    // every way to make a PvString keeps it valid utf-8
+    #[cfg(not(feature = "safe"))]
+    pub(crate) fn get_str(&self) -> &str {
+        unsafe {std::str::from_utf8_unchecked(self.get_bytes())}
+    }
+
+    // the "safe" feature means no unsafe code, so check it anyway
+    #[cfg(feature = "safe")]
+    pub(crate) fn get_str(&self) -> &str {
+        std::str::from_utf8(self.get_bytes()).unwrap()
+    }
+
+    pub fn concat(self, other: &PvString) -> Self {
+        let len = self.len() + other.len();
+        match self.data {
+            PvStringRepr::Inline(oldlen, mut bytes) if len <= INLINE_CAP => {
+                bytes[oldlen as usize..len].copy_from_slice(other.get_bytes());
+                PvString {data: PvStringRepr::Inline(len as u8, bytes)}
+            },
+            PvStringRepr::Heap(mut data) => {
+                data.extend_from_slice(other.get_bytes());
+                PvString {data: PvStringRepr::Heap(data)}
+            },
+            PvStringRepr::Inline(..) => {
+                let mut data = PvpArray::new_empty_sized(len * 2);
+                data.extend_from_slice(self.get_bytes());
+                data.extend_from_slice(other.get_bytes());
+                PvString {data: PvStringRepr::Heap(data)}
+            },
+        }
+    }
+
+    // bytes `start` up to (not including) `end`
+    // both have to be on char boundaries
+    // long ones share this string's allocation, and short ones are copied inline
+    fn substr(&self, start: usize, end: usize) -> PvString {
+        let s = self.get_str();
+        assert!(s.is_char_boundary(start) && s.is_char_boundary(end));
+        match &self.data {
+            PvStringRepr::Heap(data) if end - start > INLINE_CAP => PvString {data: PvStringRepr::Heap(data.slice(start, end))},
+            _ => PvString::new(&s[start..end]),
         }
-        out.get_data_mut().copy_from_slice(str.as_bytes());
-        out
     }
 
-    // move one copy of this string out and resize its allocation
-    // will reuse the old allocation if possible
-    unsafe fn resize_move(mut self, newsize: usize) -> Self {
-        let data = *self.data;
+    // `part` has to be a piece of self.get_str()
+    fn substr_of(&self, part: &str) -> PvString {
+        let start = part.as_ptr() as usize - self.get_str().as_ptr() as usize;
+        self.substr(start, start + part.len())
+    }
 
-        if data.refcount == 1 {
-            assert!(newsize >= data.len); // just a suggestion
+    pub fn as_str(&self) -> &str {
+        self.get_str()
+    }
 
-            let oldlayout = PvString::get_layout(data.alloc_size);
+    // in bytes
+    pub fn len(&self) -> usize {
+        self.get_str().len()
+    }
 
-            let newlayout = PvString::get_layout(newsize);
-            
-            self.data = std::alloc::realloc(self.data as *mut u8, oldlayout, newlayout.size()) as *mut PvStringData;
+    pub fn is_empty(&self) -> bool {
+        self.get_str().is_empty()
+    }
 
-            (*self.data).alloc_size = newsize;
+    // in codepoints
+    pub fn char_len(&self) -> usize {
+        self.get_str().chars().count()
+    }
 
-            self
+    // the codepoints from `start` up to (not including) `end`
+    // negative indices count from the end, and both are clamped to the string like jq does
+    pub fn slice(&self, start: isize, end: isize) -> PvString {
+        let len = self.char_len() as isize;
+        let clamp = |i: isize| (if i < 0 {i + len} else {i}).clamp(0, len) as usize;
+        let (start, end) = (clamp(start), clamp(end));
+        if start >= end {
+            return PvString::new("");
+        }
+        // turn the codepoint indices into byte offsets
+        let s = self.get_str();
+        let offset = |i: usize| s.char_indices().nth(i).map_or(s.len(), |(offset, _)| offset);
+        self.substr(offset(start), offset(end))
+    }
+
+    // like jq, splitting "" gives [] and splitting on "" gives every codepoint
+    pub fn split(&self, separator: &PvString) -> PvArray {
+        let s = self.get_str();
+        let mut out = PvArray::new_empty();
+        if s.is_empty() {
+            return out;
+        }
+        if separator.is_empty() {
+            for (i, c) in s.char_indices() {
+                out.append(self.substr(i, i + c.len_utf8()).into());
+            }
         } else {
-            let out = PvString::new_empty_sized(newsize);
-            (*out.data).len = data.len;
-            out.get_data_mut().copy_from_slice(self.get_data_mut());
-            out
+            for part in s.split(separator.get_str()) {
+                out.append(self.substr_of(part).into());
+            }
         }
+        out
     }
 
-    // get a mutable slice reference to the string data
-    // only use when refcount = 1
-    fn get_data_mut(&self) -> &mut [u8] {
-        let data = unsafe {*self.data};
+    // join `parts` with this string between them
+    // nulls are empty, numbers and booleans are written as json, and strings are used as is
+    // None if there's anything else
+    pub fn join(&self, parts: &PvArray) -> Option<PvString> {
+        let mut out = String::new();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                out.push_str(self.get_str());
+            }
+            match part {
+                Pv::Null(_) => {},
+                Pv::String(v) => out.push_str(v.get_str()),
+                Pv::Bool(_) | Pv::Int(_) | Pv::Float(_) => out.push_str(&part.to_json()),
+                _ => return None,
+            }
+        }
+        Some(PvString::new(&out))
+    }
+
+    pub fn starts_with(&self, prefix: &PvString) -> bool {
+        self.get_str().starts_with(prefix.get_str())
+    }
+
+    pub fn ends_with(&self, suffix: &PvString) -> bool {
+        self.get_str().ends_with(suffix.get_str())
+    }
 
-        let layout = std::alloc::Layout::new::<PvStringData>();
-        // no errors need to be handled
-        // the total size does not overflow isize (i'm assuming this one)
-        let (_, offset) = layout.extend(
-            // align (1) is not zero
-            // align (1) is a power of two
-            // size (data.len) does not overflow usize when rounded up to align (i'm assuming this one)
-            std::alloc::Layout::from_size_align(data.len, 1).unwrap()
-        ).unwrap();
-        // i could probably just get the size of layout
-        // but just to be safe :)
+    // unchanged if the prefix isn't there
+    pub fn ltrimstr(self, prefix: &PvString) -> PvString {
+        match self.get_str().strip_prefix(prefix.get_str()) {
+            Some(rest) => self.substr_of(rest),
+            None => self,
+        }
+    }
 
-        unsafe {
-            std::slice::from_raw_parts_mut(
-                (self.data as *mut u8).add(offset),
-                data.len
-            )
+    // unchanged if the suffix isn't there
+    pub fn rtrimstr(self, suffix: &PvString) -> PvString {
+        match self.get_str().strip_suffix(suffix.get_str()) {
+            Some(rest) => self.substr_of(rest),
+            None => self,
         }
     }
 
-    // get an immutable reference to the string data
-    // only use when refcount = 1
-    fn get_str(&self) -> &str {
-        // maybe use std::str::from_utf8_unchecked()?
-        // (because the "only" way to get a PvString is from a &str)
-        std::str::from_utf8(self.get_data_mut()).unwrap()
+    // the same whitespace jq trims
+    fn is_trim_whitespace(c: char) -> bool {
+        matches!(c, ' ' | '\t' | '\n' | '\r' | '\u{b}' | '\u{c}')
     }
 
-    pub fn concat(self, other: &PvString) -> Self {
-        let data = unsafe {*self.data};
-        let otherdata = unsafe {*other.data};
+    pub fn trim(&self) -> PvString {
+        self.substr_of(self.get_str().trim_matches(PvString::is_trim_whitespace))
+    }
 
-        let s = if data.refcount == 1 && data.alloc_size >= data.len + otherdata.len {
-            self
-        } else {
-            unsafe {
-                self.resize_move((data.len + otherdata.len) * 2)
-            }
-        };
+    pub fn ltrim(&self) -> PvString {
+        self.substr_of(self.get_str().trim_start_matches(PvString::is_trim_whitespace))
+    }
 
-        unsafe {
-            (*s.data).len += otherdata.len;
+    pub fn rtrim(&self) -> PvString {
+        self.substr_of(self.get_str().trim_end_matches(PvString::is_trim_whitespace))
+    }
+
+    // only a-z and A-Z are changed
+    pub fn ascii_downcase(&self) -> PvString {
+        PvString::new(&self.get_str().to_ascii_lowercase())
+    }
+
+    pub fn ascii_upcase(&self) -> PvString {
+        PvString::new(&self.get_str().to_ascii_uppercase())
+    }
+
+    // an array of codepoints
+    pub fn explode(&self) -> PvArray {
+        let mut out = PvArray::new_empty_sized(self.len());
+        for c in self.get_str().chars() {
+            out.append(Pv::int(c as isize));
         }
+        out
+    }
 
-        s.get_data_mut()[data.len..].copy_from_slice(other.get_data_mut());
+    // None if anything in `codepoints` isn't a valid codepoint
+    pub fn implode(codepoints: &PvArray) -> Option<PvString> {
+        let mut out = String::new();
+        for codepoint in codepoints {
+            let Pv::Int(codepoint) = codepoint else {
+                return None;
+            };
+            let codepoint = u32::try_from(codepoint.to_isize()?).ok()?;
+            out.push(char::from_u32(codepoint)?);
+        }
+        Some(PvString::new(&out))
+    }
 
-        s
+    // the codepoint index of every (possibly overlapping) occurrence of `needle`
+    pub fn indices(&self, needle: &PvString) -> PvArray {
+        let s = self.get_str();
+        let needle = needle.get_str();
+        let mut out = PvArray::new_empty();
+        if needle.is_empty() {
+            return out;
+        }
+        for (index, (i, _)) in s.char_indices().enumerate() {
+            if s[i..].starts_with(needle) {
+                out.append(Pv::int(index as isize));
+            }
+        }
+        out
     }
 }
 
 impl std::fmt::Debug for PvString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let data = unsafe {*self.data};
         f.debug_struct("PvString")
-         .field("len", &data.len)
-         .field("alloc_size", &data.alloc_size)
+         .field("len", &self.len())
+         .field("alloc_size", &match &self.data {
+             PvStringRepr::Inline(..) => INLINE_CAP,
+             PvStringRepr::Heap(data) => data.alloc_size(),
+         })
          .field("data", &self.get_str())
          .finish()
     }
 }
 
-impl Drop for PvString {
-    fn drop(&mut self) {
-        if (decref!(self)) {
-            let size = unsafe {(*self.data).alloc_size};
-            let layout = PvString::get_layout(size);
+impl Eq for PvString {}
 
-            unsafe {std::alloc::dealloc(self.data as *mut u8, layout);}
+impl PartialEq for PvString {
+    fn eq(&self, other: &PvString) -> bool {
+        if let (PvStringRepr::Heap(data1), PvStringRepr::Heap(data2)) = (&self.data, &other.data) {
+            if data1.ptr_eq(data2) {
+                // interned strings (and clones) don't need to compare the bytes
+                return true;
+            }
         }
+        self.get_str() == other.get_str()
     }
 }
 
-impl Clone for PvString {
-    fn clone(&self) -> Self {
-        incref!(self);
-        PvString {data: self.data}
+// codepoint order, which is the same as byte order for utf-8
+impl PartialOrd for PvString {
+    fn partial_cmp(&self, other: &PvString) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-impl PartialEq for PvString {
-    fn eq(&self, other: &PvString) -> bool {
-        self.get_str() == other.get_str()
+impl Ord for PvString {
+    fn cmp(&self, other: &PvString) -> std::cmp::Ordering {
+        self.get_str().cmp(other.get_str())
     }
 }
 
+// this has to agree with strings that aren't interned, so it always hashes the bytes
 impl std::hash::Hash for PvString {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.get_str().hash(state);
     }
 }
 
+// so the intern table can be searched with a &str
+// Eq and Hash both only look at the text, so this agrees with them
+impl std::borrow::Borrow<str> for PvString {
+    fn borrow(&self) -> &str {
+        self.get_str()
+    }
+}
+
 impl From<&str> for PvString {
     fn from(value: &str) -> Self {
         PvString::new(value)